@@ -0,0 +1,254 @@
+use std::fmt::Display;
+use std::io;
+
+use crate::exp::{Exp, Location};
+use crate::parser::Rule;
+
+pub type Result<T> = std::result::Result<T, LispicoError>;
+
+#[derive(Debug)]
+pub enum LispicoError {
+    TypeMismatch {
+        expected: &'static str,
+        found: Exp,
+    },
+    ArityMismatch {
+        operator: String,
+        expected: Arity,
+        found: usize,
+    },
+    UnknownOperator(String),
+    DivisionByZero,
+    Io(io::Error),
+    Parse(Box<pest::error::Error<Rule>>),
+    UnterminatedExpression(Location),
+    ImportFailed {
+        path: String,
+        source: Box<LispicoError>,
+    },
+    // A script given to the interpreter could not be read.
+    ReadFailed {
+        path: String,
+        source: io::Error,
+    },
+    Custom(String),
+    Traced {
+        // Innermost call first.
+        trace: Vec<Frame>,
+        source: Box<LispicoError>,
+    },
+}
+
+// The number of arguments an operator accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exactly(n) => count == n,
+            Arity::AtLeast(min) => count >= min,
+            Arity::Between(min, max) => (min..=max).contains(&count),
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exactly(n) => write!(f, "{n}"),
+            Arity::AtLeast(min) => write!(f, "at least {min}"),
+            Arity::Between(min, max) => write!(f, "{min} to {max}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub operator: String,
+    pub args: Vec<Exp>,
+    pub location: Option<Location>,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{location}: ")?,
+            None => write!(f, "<unknown>: ")?,
+        }
+        write!(f, "({}", self.operator)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+pub struct Traceback<'a>(&'a LispicoError);
+
+impl Traceback<'_> {
+    const MAX_FRAMES: usize = 32;
+
+    fn frames(&self) -> Vec<&Frame> {
+        let mut frames = Vec::new();
+        let mut err = self.0;
+        loop {
+            match err {
+                LispicoError::Traced { trace, source } => {
+                    frames.extend(trace.iter().rev());
+                    err = source;
+                }
+                LispicoError::ImportFailed { source, .. } => err = source,
+                _ => return frames,
+            }
+        }
+    }
+
+    fn cause(&self) -> &LispicoError {
+        let mut err = self.0;
+        loop {
+            match err {
+                LispicoError::Traced { source, .. } => err = source,
+                LispicoError::ImportFailed { source, .. } => err = source,
+                _ => return err,
+            }
+        }
+    }
+}
+
+impl Display for Traceback<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let frames = self.frames();
+        if !frames.is_empty() {
+            writeln!(f, "Traceback (most recent call last):")?;
+        }
+        let half = Traceback::MAX_FRAMES / 2;
+        for (i, frame) in frames.iter().enumerate() {
+            if frames.len() > Traceback::MAX_FRAMES && i >= half && i < frames.len() - half {
+                if i == half {
+                    writeln!(f, "  ... {} more calls ...", frames.len() - 2 * half)?;
+                }
+                continue;
+            }
+            writeln!(f, "  {frame}")?;
+        }
+        write!(f, "Error: {}", self.cause())
+    }
+}
+
+impl LispicoError {
+    pub fn type_mismatch(expected: &'static str, found: &Exp) -> Self {
+        LispicoError::TypeMismatch {
+            expected,
+            found: found.clone(),
+        }
+    }
+
+    // Records that the error propagated out of the given call.
+    pub fn in_call(self, frame: Frame) -> Self {
+        match self {
+            LispicoError::Traced { mut trace, source } => {
+                trace.push(frame);
+                LispicoError::Traced { trace, source }
+            }
+            _ => LispicoError::Traced {
+                trace: vec![frame],
+                source: Box::new(self),
+            },
+        }
+    }
+
+    // The location of the innermost form that failed.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            LispicoError::Traced { trace, .. } => {
+                trace.iter().find_map(|frame| frame.location.as_ref())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn trace(&self) -> &[Frame] {
+        match self {
+            LispicoError::Traced { trace, .. } => trace,
+            _ => &[],
+        }
+    }
+
+    // The underlying error, without the calls it propagated through.
+    pub fn root(&self) -> &LispicoError {
+        match self {
+            LispicoError::Traced { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    pub fn traceback(&self) -> Traceback<'_> {
+        Traceback(self)
+    }
+}
+
+impl Display for LispicoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LispicoError::TypeMismatch { expected, found } => {
+                write!(f, "Expected {expected}, but got {found}")
+            }
+            LispicoError::ArityMismatch {
+                operator,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{operator} expects {expected} argument(s), but got {found}"
+            ),
+            LispicoError::UnknownOperator(name) => write!(f, "Unknown operator: {name}"),
+            LispicoError::DivisionByZero => write!(f, "Division by zero"),
+            LispicoError::Io(e) => write!(f, "{e}"),
+            LispicoError::Parse(e) => write!(f, "Parse error:\n{e}"),
+            LispicoError::UnterminatedExpression(location) => {
+                write!(f, "{location}: Unterminated expression")
+            }
+            LispicoError::ImportFailed { path, source } => {
+                write!(f, "Failed to import \"{path}\": {source}")
+            }
+            LispicoError::ReadFailed { path, source } => {
+                write!(f, "Failed to read \"{path}\": {source}")
+            }
+            LispicoError::Custom(message) => write!(f, "{message}"),
+            LispicoError::Traced { source, .. } => match self.location() {
+                Some(location) => write!(f, "{location}: {source}"),
+                None => write!(f, "{source}"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for LispicoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LispicoError::Io(e) => Some(e),
+            LispicoError::Parse(e) => Some(e.as_ref()),
+            LispicoError::ImportFailed { source, .. } => Some(source.as_ref()),
+            LispicoError::ReadFailed { source, .. } => Some(source),
+            LispicoError::Traced { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LispicoError {
+    fn from(e: io::Error) -> Self {
+        LispicoError::Io(e)
+    }
+}
+
+impl From<pest::error::Error<Rule>> for LispicoError {
+    fn from(e: pest::error::Error<Rule>) -> Self {
+        LispicoError::Parse(Box::new(e))
+    }
+}
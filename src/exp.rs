@@ -1,13 +1,27 @@
+use std::collections::VecDeque;
 use std::fmt::Display;
+use std::rc::Rc;
 
-use crate::predefined::PREDEFINED_OPERATORS;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+pub use crate::error::{Arity, Frame, LispicoError, Result};
+use crate::predefined::{eval_args, eval_sequence, operator_step};
+
+// The canonical true value, returned by predicates. It evaluates to itself and cannot be rebound.
+// `()` is the only false value, and any other value counts as true.
+pub const TRUE: &str = "t";
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Atom {
     Identifier(String),
+    // Read from number literals without a decimal point.
+    Integer(i64),
+    // Integers that do not fit in an `Integer`.
+    BigInteger(BigInt),
+    // Always with a denominator other than 1.
+    Rational(BigRational),
     Number(f64),
     String(String),
 }
@@ -16,30 +30,72 @@ impl Atom {
     pub fn as_identifier(&self) -> Result<&str> {
         match self {
             Atom::Identifier(id) => Ok(id),
-            _ => Err("Expected an identifier".into()),
+            _ => Err(self.type_mismatch("an identifier")),
+        }
+    }
+
+    pub fn as_integer(&self) -> Result<i64> {
+        match self {
+            Atom::Integer(num) => Ok(*num),
+            _ => Err(self.type_mismatch("an integer")),
         }
     }
 
+    // Exact numbers are converted, possibly losing precision.
     pub fn as_number(&self) -> Result<f64> {
         match self {
+            Atom::Integer(num) => Ok(*num as f64),
+            Atom::BigInteger(num) => Ok(num.to_f64().unwrap_or(f64::NAN)),
+            Atom::Rational(num) => Ok(num.to_f64().unwrap_or(f64::NAN)),
             Atom::Number(num) => Ok(*num),
-            _ => Err("Expected a number".into()),
+            _ => Err(self.type_mismatch("a number")),
+        }
+    }
+
+    // The value of an exact number, which floats are not.
+    pub fn as_exact(&self) -> Option<BigRational> {
+        match self {
+            Atom::Integer(num) => Some(BigRational::from_integer(BigInt::from(*num))),
+            Atom::BigInteger(num) => Some(BigRational::from_integer(num.clone())),
+            Atom::Rational(num) => Some(num.clone()),
+            _ => None,
+        }
+    }
+
+    // The smallest representation of an exact number.
+    pub fn exact(num: BigRational) -> Atom {
+        if !num.is_integer() {
+            return Atom::Rational(num);
+        }
+        let num = num.to_integer();
+        match num.to_i64() {
+            Some(small) => Atom::Integer(small),
+            None => Atom::BigInteger(num),
         }
     }
 
     pub fn as_string(&self) -> Result<&str> {
         match self {
             Atom::String(s) => Ok(s),
-            _ => Err("Expected a string".into()),
+            _ => Err(self.type_mismatch("a string")),
         }
     }
 
+    fn type_mismatch(&self, expected: &'static str) -> LispicoError {
+        LispicoError::type_mismatch(expected, &Exp::Atom(self.clone()))
+    }
+
     pub fn eval(&self, env: List) -> Result<(Exp, List)> {
         match self {
+            Atom::Identifier(identifier) if identifier == TRUE => {
+                Ok((Exp::Atom(self.clone()), env))
+            }
             Atom::Identifier(identifier) => {
-                let res = env_lookup(identifier, &env);
+                let res = env_lookup(identifier, &env)?;
                 Ok((res, env))
             }
+            Atom::Integer(num) => Ok((Exp::Atom(Atom::Integer(*num)), env)),
+            Atom::BigInteger(_) | Atom::Rational(_) => Ok((Exp::Atom(self.clone()), env)),
             Atom::Number(num) => Ok((Exp::Atom(Atom::Number(*num)), env)),
             Atom::String(s) => Ok((Exp::Atom(Atom::String(s.to_string())), env)),
         }
@@ -50,8 +106,32 @@ impl Display for Atom {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Atom::Identifier(identifier) => write!(f, "{identifier}"),
-            Atom::Number(num) => write!(f, "{num}"),
-            Atom::String(s) => write!(f, "\"{s}\""),
+            Atom::Integer(num) => write!(f, "{num}"),
+            Atom::BigInteger(num) => write!(f, "{num}"),
+            Atom::Rational(num) => write!(f, "{num}"),
+            // Always printed with a decimal point or an exponent, unlike integers.
+            Atom::Number(num) if num.is_nan() => write!(f, "+nan.0"),
+            Atom::Number(num) if num.is_infinite() => {
+                write!(f, "{}inf.0", if *num > 0.0 { "+" } else { "-" })
+            }
+            // Written as the reader accepts it, possibly with an exponent.
+            Atom::Number(num) => write!(f, "{num:?}"),
+            Atom::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\0' => write!(f, "\\0")?,
+                        c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
         }
     }
 }
@@ -59,29 +139,57 @@ impl Display for Atom {
 #[derive(Debug, PartialEq, Clone)]
 pub enum List {
     Nil,
-    Cons(Box<Exp>, Box<List>),
+    Cons(Rc<Exp>, Rc<List>),
+}
+
+// Long lists are freed a node at a time, where the derived drop would recurse down the tail and
+// overflow the stack.
+impl Drop for List {
+    fn drop(&mut self) {
+        let List::Cons(_, tl) = self else {
+            return;
+        };
+        let mut next = std::mem::replace(tl, Rc::new(List::Nil));
+        while let Ok(mut list) = Rc::try_unwrap(next) {
+            match &mut list {
+                List::Cons(_, tl) => next = std::mem::replace(tl, Rc::new(List::Nil)),
+                List::Nil => break,
+            }
+        }
+    }
 }
 
 impl List {
     pub fn hd(&self) -> Result<&Exp> {
         match self {
             List::Cons(hd, _) => Ok(hd),
-            List::Nil => Err("Expected a cons, but got an atom".into()),
+            List::Nil => Err(LispicoError::type_mismatch(
+                "a non-empty list",
+                &Exp::List(List::Nil),
+            )),
         }
     }
 
     pub fn tl(&self) -> Result<&List> {
         match self {
             List::Cons(_, tl) => Ok(tl),
-            List::Nil => Err("Expected a cons, but got an atom".into()),
+            List::Nil => Err(LispicoError::type_mismatch(
+                "a non-empty list",
+                &Exp::List(List::Nil),
+            )),
         }
     }
 
     pub fn len(&self) -> usize {
-        match self {
-            List::Nil => 0,
-            List::Cons(_, tl) => 1 + tl.len(),
-        }
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, List::Nil)
+    }
+
+    pub fn iter(&self) -> ListIter<'_> {
+        ListIter(self)
     }
 
     pub fn nth(&self, n: usize) -> Result<&Exp> {
@@ -98,14 +206,40 @@ impl List {
     pub fn extend(&self, other: &List) -> List {
         match self {
             List::Nil => other.clone(),
-            List::Cons(hd, tl) => List::Cons(hd.clone(), Box::new(tl.extend(other))),
+            List::Cons(hd, tl) => List::Cons(hd.clone(), Rc::new(tl.extend(other))),
         }
     }
 
     pub fn eval(&self, env: List) -> Result<(Exp, List)> {
         match self {
             List::Nil => Ok((Exp::List(List::Nil), env)),
-            List::Cons(hd, tl) => eval_function(hd, tl, env),
+            List::Cons(_, _) => Exp::List(self.clone()).eval(env),
+        }
+    }
+}
+
+impl FromIterator<Exp> for List {
+    fn from_iter<I: IntoIterator<Item = Exp>>(iter: I) -> List {
+        let items: Vec<Exp> = iter.into_iter().collect();
+        items
+            .into_iter()
+            .rev()
+            .fold(List::Nil, |tl, hd| List::Cons(Rc::new(hd), Rc::new(tl)))
+    }
+}
+
+pub struct ListIter<'a>(&'a List);
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = &'a Exp;
+
+    fn next(&mut self) -> Option<&'a Exp> {
+        match self.0 {
+            List::Nil => None,
+            List::Cons(hd, tl) => {
+                self.0 = tl;
+                Some(hd)
+            }
         }
     }
 }
@@ -124,42 +258,680 @@ impl Display for List {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub file: Rc<str>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+// A function created by `->`, closed over the environment it was defined in.
+#[derive(Clone)]
+pub struct Closure {
+    pub params: List,
+    // The parameters as parsed when the closure was created.
+    pub signature: Rc<Params>,
+    // Evaluated in sequence, as by `do`.
+    pub body: List,
+    pub env: List,
+    // The recursive definitions this closure was defined with, bound again on every call.
+    pub group: Option<Rc<Vec<(Exp, Exp)>>>,
+}
+
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure")
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Closure {
+    // Binds the parameters to the arguments of a call to the function with the given name.
+    pub fn bind_args(&self, args: &[Exp], name: impl FnOnce() -> String) -> Result<List> {
+        let env = match &self.group {
+            Some(group) => bind_group(group, self.env.clone()),
+            None => self.env.clone(),
+        };
+        let arity = self.signature.arity();
+        if !arity.accepts(args.len()) {
+            return Err(LispicoError::ArityMismatch {
+                operator: name(),
+                expected: arity,
+                found: args.len(),
+            });
+        }
+        self.signature.bind(args, env)
+    }
+
+    // Runs a macro transformer on the forms it was called with.
+    fn expand(&self, forms: &[Exp], name: impl FnOnce() -> String) -> Result<Exp> {
+        let env = self.bind_args(forms, name)?;
+        let (expansion, _) = eval_sequence(&self.body, env)?.run()?;
+        Ok(expansion)
+    }
+
+    fn write_form(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        operator: &str,
+        env_param: Option<&str>,
+    ) -> std::fmt::Result {
+        write!(f, "({operator} ({})", self.params)?;
+        if let Some(env_param) = env_param {
+            write!(f, " {env_param}")?;
+        }
+        for form in self.body.iter() {
+            write!(f, " {form}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Display for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_form(f, "->", None)
+    }
+}
+
+pub type HostFn = dyn Fn(&List, List) -> Result<(Exp, List)>;
+
+// An operator registered by the embedding application, bound in the environment of the interpreter
+// it was registered with.
+pub struct HostOperator {
+    pub name: String,
+    pub func: Box<HostFn>,
+}
+
+impl std::fmt::Debug for HostOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostOperator")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Exp {
     Atom(Atom),
     List(List),
+    // A form read from source, annotated with where it was read from.
+    // Locations are ignored when comparing expressions.
+    Located(Box<Exp>, Rc<Location>),
+    Closure(Rc<Closure>),
+    // A transformer created by `macro`, called with the forms of its arguments to build the form
+    // that is evaluated in their place.
+    Macro(Rc<Closure>),
+    // An operator created by `operative`, called with the forms of its operands and with the
+    // caller's environment bound to the named parameter.
+    Operative(Rc<Closure>, Rc<str>),
+    Host(Rc<HostOperator>),
+}
+
+impl PartialEq for Exp {
+    fn eq(&self, other: &Exp) -> bool {
+        match (self.unlocated(), other.unlocated()) {
+            (Exp::Atom(lhs), Exp::Atom(rhs)) => lhs == rhs,
+            (Exp::List(lhs), Exp::List(rhs)) => lhs == rhs,
+            (Exp::Closure(lhs), Exp::Closure(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Exp::Macro(lhs), Exp::Macro(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Exp::Operative(lhs, _), Exp::Operative(rhs, _)) => Rc::ptr_eq(lhs, rhs),
+            (Exp::Host(lhs), Exp::Host(rhs)) => Rc::ptr_eq(lhs, rhs),
+            _ => false,
+        }
+    }
 }
 
 impl Exp {
     pub fn as_atom(&self) -> Result<&Atom> {
-        match self {
+        match self.unlocated() {
             Exp::Atom(atom) => Ok(atom),
-            _ => Err("Expected an identifier, but got a list".into()),
+            _ => Err(LispicoError::type_mismatch("an atom", self)),
         }
     }
 
     pub fn as_list(&self) -> Result<&List> {
-        match self {
+        match self.unlocated() {
             Exp::List(list) => Ok(list),
-            _ => Err("Expected a list, but got an identifier".into()),
+            _ => Err(LispicoError::type_mismatch("a list", self)),
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Exp::Located(_, location) => Some(location),
+            _ => None,
+        }
+    }
+
+    pub fn truth(holds: bool) -> Exp {
+        if holds {
+            Exp::Atom(Atom::Identifier(TRUE.to_string()))
+        } else {
+            Exp::List(List::Nil)
+        }
+    }
+
+    pub fn is_true(&self) -> bool {
+        !matches!(self.unlocated(), Exp::List(List::Nil))
+    }
+
+    pub fn unlocated(&self) -> &Exp {
+        match self {
+            Exp::Located(exp, _) => exp.unlocated(),
+            _ => self,
+        }
+    }
+
+    pub fn strip_locations(&self) -> Exp {
+        match self {
+            Exp::Atom(atom) => Exp::Atom(atom.clone()),
+            Exp::List(list) => Exp::List(list.strip_locations()),
+            Exp::Located(exp, _) => exp.strip_locations(),
+            Exp::Closure(closure) => Exp::Closure(closure.clone()),
+            Exp::Macro(transformer) => Exp::Macro(transformer.clone()),
+            Exp::Operative(operative, env_param) => {
+                Exp::Operative(operative.clone(), env_param.clone())
+            }
+            Exp::Host(operator) => Exp::Host(operator.clone()),
         }
     }
 }
 
-fn env_lookup(identifier: &str, env: &List) -> Exp {
+impl List {
+    fn strip_locations(&self) -> List {
+        match self {
+            List::Nil => List::Nil,
+            List::Cons(hd, tl) => {
+                List::Cons(Rc::new(hd.strip_locations()), Rc::new(tl.strip_locations()))
+            }
+        }
+    }
+}
+
+// Environments given to `eval` are built by user code, so a binding may lack its value.
+fn env_lookup(identifier: &str, env: &List) -> Result<Exp> {
     match env {
-        List::Nil => Exp::List(List::Nil),
-        List::Cons(hd, tl) => match **hd {
-            Exp::List(List::Cons(ref name, ref value_list)) if matches!(**name, Exp::Atom(Atom::Identifier(ref id)) if id == identifier) =>
-            {
-                debug_assert!(value_list.hd().is_ok());
-                return value_list.hd().unwrap().clone();
+        List::Nil => Ok(Exp::List(List::Nil)),
+        List::Cons(hd, tl) => match hd.unlocated() {
+            Exp::List(List::Cons(name, value_list)) if matches!(name.unlocated(), Exp::Atom(Atom::Identifier(ref id)) if id == identifier) => {
+                match value_list.hd() {
+                    Ok(value) => Ok(value.clone()),
+                    Err(_) => Err(LispicoError::type_mismatch("a binding", hd)),
+                }
             }
             _ => env_lookup(identifier, tl),
         },
     }
 }
 
+// How evaluation continues once an operator has been applied.
+pub enum Step {
+    // The operator produced a value, along with the environment that follows it.
+    Done(Exp, List),
+    // The value is that of `exp` evaluated in `env`, which is in tail position. `scope` is the
+    // environment to continue with afterwards, if the operator opened a scope of its own.
+    Tail {
+        exp: Exp,
+        env: List,
+        scope: Option<List>,
+    },
+}
+
+impl Step {
+    // Continues with the given environment once the step is evaluated.
+    pub fn in_scope(self, env: List) -> Step {
+        match self {
+            Step::Done(res, _) => Step::Done(res, env),
+            Step::Tail {
+                exp,
+                env: inner_env,
+                scope,
+            } => Step::Tail {
+                exp,
+                env: inner_env,
+                scope: Some(scope.unwrap_or(env)),
+            },
+        }
+    }
+
+    pub fn run(self) -> Result<(Exp, List)> {
+        match self {
+            Step::Done(res, env) => Ok((res, env)),
+            Step::Tail { exp, env, scope } => {
+                let (res, env) = exp.eval(env)?;
+                Ok((res, scope.unwrap_or(env)))
+            }
+        }
+    }
+}
+
+// How many of the calls replaced by tail calls are kept to trace an error.
+const MAX_TAIL_CALLS: usize = 16;
+
+impl Exp {
+    // Expressions in tail position are evaluated by looping rather than recursing, so that tail calls
+    // run in constant stack space.
+    pub fn eval(&self, env: List) -> Result<(Exp, List)> {
+        if let Exp::Atom(atom) = self {
+            return atom.eval(env);
+        }
+        let mut exp = self.clone();
+        let mut env = env;
+        let mut scope: Option<List> = None;
+        let mut location = None;
+        // Most recent last.
+        let mut tail_calls: VecDeque<Call> = VecDeque::new();
+        // Errors are reported at the calls that led to them, atoms having no location of their own.
+        let trace = |e: LispicoError, tail_calls: &VecDeque<Call>| {
+            tail_calls
+                .iter()
+                .rev()
+                .fold(e, |e, call| e.in_call(call.frame()))
+        };
+        loop {
+            let (operator, args) = match exp {
+                Exp::Located(inner, loc) => {
+                    exp = *inner;
+                    location = Some(loc);
+                    continue;
+                }
+                Exp::List(List::Cons(ref operator, ref args)) => (operator.clone(), args.clone()),
+                Exp::Atom(atom) => {
+                    let (res, env) = atom.eval(env).map_err(|e| trace(e, &tail_calls))?;
+                    return Ok((res, scope.unwrap_or(env)));
+                }
+                Exp::List(List::Nil)
+                | Exp::Closure(_)
+                | Exp::Macro(_)
+                | Exp::Operative(..)
+                | Exp::Host(_) => return Ok((exp, scope.unwrap_or(env))),
+            };
+
+            let step = eval_call(operator, args, env, location.take());
+            match step.map_err(|e| trace(e, &tail_calls))? {
+                (Step::Done(res, env), _) => return Ok((res, scope.unwrap_or(env))),
+                (
+                    Step::Tail {
+                        exp: next,
+                        env: next_env,
+                        scope: next_scope,
+                    },
+                    call,
+                ) => {
+                    exp = next;
+                    env = next_env;
+                    scope = scope.or(next_scope);
+                    if tail_calls.len() == MAX_TAIL_CALLS {
+                        tail_calls.pop_front();
+                    }
+                    tail_calls.push_back(call);
+                }
+            }
+        }
+    }
+}
+
+impl Display for Exp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exp::Atom(atom) => write!(f, "{atom}"),
+            Exp::List(List::Cons(hd, tl)) if matches!(**hd, Exp::Atom(Atom::Identifier(ref id)) if id == "'") =>
+            {
+                write!(f, "'{tl}")
+            }
+            Exp::List(List::Cons(hd, tl)) if matches!(**hd, Exp::Atom(Atom::Identifier(ref id)) if id == "`") =>
+            {
+                write!(f, "`")?;
+                write_template_list(f, tl)
+            }
+            Exp::List(list) => write!(f, "({list})"),
+            Exp::Located(exp, _) => write!(f, "{exp}"),
+            Exp::Closure(closure) => write!(f, "{closure}"),
+            Exp::Macro(transformer) => transformer.write_form(f, "macro", None),
+            Exp::Operative(operative, env_param) => {
+                operative.write_form(f, "operative", Some(env_param))
+            }
+            Exp::Host(operator) => write!(f, "{}", operator.name),
+        }
+    }
+}
+
+// Prints a template back as it is written, undoing the expansion made by the parser.
+fn write_template(f: &mut std::fmt::Formatter<'_>, exp: &Exp) -> std::fmt::Result {
+    if let Exp::List(List::Nil) = exp.unlocated() {
+        return write!(f, "()");
+    }
+    if let Some(quoted) = call_args(exp, "'") {
+        return write!(f, "{quoted}");
+    }
+    if let Some(items) = call_args(exp, "`") {
+        return write_template_list(f, items);
+    }
+    if let Some(spliced) = call_args(exp, ",@") {
+        return write!(f, ",@{spliced}");
+    }
+    write!(f, ",{exp}")
+}
+
+fn write_template_list(f: &mut std::fmt::Formatter<'_>, items: &List) -> std::fmt::Result {
+    // A quoted template is built as a list of `'` and the template.
+    let quote = Exp::Atom(Atom::Identifier("'".to_string()));
+    if let (Ok(first), Ok(template), 2) = (items.nth(0), items.nth(1), items.len()) {
+        if call_args(first, "'").and_then(|args| args.hd().ok()) == Some(&quote) {
+            write!(f, "'")?;
+            return write_template(f, template);
+        }
+    }
+    write!(f, "(")?;
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write_template(f, item)?;
+    }
+    write!(f, ")")
+}
+
+// The arguments of a call to the given operator, if the expression is one.
+fn call_args<'a>(exp: &'a Exp, operator: &str) -> Option<&'a List> {
+    match exp.unlocated() {
+        Exp::List(List::Cons(hd, tl)) if matches!(hd.unlocated(), Exp::Atom(Atom::Identifier(id)) if id == operator) => {
+            Some(tl)
+        }
+        _ => None,
+    }
+}
+
+// A call being evaluated, kept to trace the errors raised by it.
+struct Call {
+    operator: Rc<Exp>,
+    function: Exp,
+    args: CallArgs,
+    location: Option<Rc<Location>>,
+}
+
+enum CallArgs {
+    // The evaluated arguments of a function call.
+    Values(Vec<Exp>),
+    // The forms an operator was applied to.
+    Forms(Rc<List>),
+}
+
+impl Call {
+    fn frame(&self) -> Frame {
+        let args = match &self.args {
+            CallArgs::Values(values) => values.clone(),
+            CallArgs::Forms(forms) => forms.iter().map(Exp::strip_locations).collect(),
+        };
+        Frame {
+            operator: call_name(&self.operator, &self.function),
+            args,
+            location: self.location.as_deref().cloned(),
+        }
+    }
+}
+
+fn call_name(operator: &Exp, function: &Exp) -> String {
+    match operator.unlocated() {
+        Exp::Atom(Atom::Identifier(name)) => name.clone(),
+        _ => function.to_string(),
+    }
+}
+
+fn eval_call(
+    operator: Rc<Exp>,
+    args: Rc<List>,
+    env: List,
+    location: Option<Rc<Location>>,
+) -> Result<(Step, Call)> {
+    let (function, env) = operator.eval(env)?;
+
+    match function {
+        Exp::Closure(ref closure) => {
+            let closure = closure.clone();
+            let (arg_values, _) = eval_args(&args, env.clone())?;
+            let inner_env = closure.bind_args(&arg_values, || call_name(&operator, &function));
+            let call = Call {
+                operator,
+                function,
+                args: CallArgs::Values(arg_values),
+                location,
+            };
+            let step = inner_env
+                .and_then(|inner_env| eval_sequence(&closure.body, inner_env))
+                .map_err(|e| e.in_call(call.frame()))?;
+            Ok((step.in_scope(env), call))
+        }
+        Exp::Macro(ref transformer) => {
+            let transformer = transformer.clone();
+            let forms: Vec<Exp> = args.iter().cloned().collect();
+            let expansion = transformer.expand(&forms, || call_name(&operator, &function));
+            let call = Call {
+                operator,
+                function,
+                args: CallArgs::Forms(args),
+                location,
+            };
+            let expansion = expansion.map_err(|e| e.in_call(call.frame()))?;
+            let step = Step::Tail {
+                exp: expansion,
+                env,
+                scope: None,
+            };
+            Ok((step, call))
+        }
+        Exp::Operative(ref operative, ref env_param) => {
+            let operative = operative.clone();
+            let forms: Vec<Exp> = args.iter().cloned().collect();
+            let inner_env = operative
+                .bind_args(&forms, || call_name(&operator, &function))
+                .map(|inner_env| {
+                    let env_name = Exp::Atom(Atom::Identifier(env_param.to_string()));
+                    env_bind(env_name, Exp::List(env.clone()), inner_env)
+                });
+            let call = Call {
+                operator,
+                function,
+                args: CallArgs::Forms(args),
+                location,
+            };
+            let step = inner_env
+                .and_then(|inner_env| eval_sequence(&operative.body, inner_env))
+                .map_err(|e| e.in_call(call.frame()))?;
+            Ok((step.in_scope(env), call))
+        }
+        Exp::Host(ref operator_value) => {
+            let res = (operator_value.func)(&args, env);
+            let call = Call {
+                operator,
+                function,
+                args: CallArgs::Forms(args),
+                location,
+            };
+            match res {
+                Ok((res, env)) => Ok((Step::Done(res, env), call)),
+                Err(e) => Err(e.in_call(call.frame())),
+            }
+        }
+        Exp::Atom(Atom::Identifier(ref identifier)) => {
+            let step = operator_step(identifier, &args, env);
+            let call = Call {
+                operator,
+                function,
+                args: CallArgs::Forms(args),
+                location,
+            };
+            match step {
+                Ok(step) => Ok((step, call)),
+                Err(e) => Err(e.in_call(call.frame())),
+            }
+        }
+        _ => {
+            let err = LispicoError::type_mismatch("an operator", &function);
+            let call = Call {
+                operator,
+                function,
+                args: CallArgs::Forms(args),
+                location,
+            };
+            Err(err.in_call(call.frame()))
+        }
+    }
+}
+
+// Expands a call to a macro once, or returns `None` if the form is not one.
+pub fn macroexpand_1(form: &Exp, env: &List) -> Result<Option<Exp>> {
+    let Exp::List(List::Cons(operator, args)) = form.unlocated() else {
+        return Ok(None);
+    };
+    let Exp::Atom(Atom::Identifier(name)) = operator.unlocated() else {
+        return Ok(None);
+    };
+    match env_lookup(name, env)? {
+        Exp::Macro(transformer) => {
+            let forms: Vec<Exp> = args.iter().cloned().collect();
+            transformer.expand(&forms, || name.clone()).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+// The parameter list of a function: required names, then optional `(name default)` pairs, then
+// possibly `. rest` to collect the remaining arguments in a list.
+pub struct Params {
+    required: Vec<Exp>,
+    optional: Vec<(Exp, Exp)>,
+    rest: Option<Exp>,
+}
+
+pub fn parse_params(params: &List) -> Result<Params> {
+    let mut res = Params {
+        required: Vec::new(),
+        optional: Vec::new(),
+        rest: None,
+    };
+    let mut params = params.iter();
+    while let Some(param) = params.next() {
+        match param.unlocated() {
+            Exp::Atom(Atom::Identifier(name)) if name == "." => {
+                let rest = params.next().ok_or_else(|| {
+                    LispicoError::Custom("Expected a rest parameter after .".to_string())
+                })?;
+                rest.as_atom()?.as_identifier()?;
+                check_bindable(rest)?;
+                if let Some(extra) = params.next() {
+                    return Err(LispicoError::Custom(format!(
+                        "Unexpected parameter {extra} after the rest parameter"
+                    )));
+                }
+                res.rest = Some(rest.clone());
+            }
+            Exp::Atom(Atom::Identifier(_)) if !res.optional.is_empty() => {
+                return Err(LispicoError::Custom(format!(
+                    "Required parameter {param} follows an optional one"
+                )));
+            }
+            Exp::Atom(Atom::Identifier(_)) => {
+                check_bindable(param)?;
+                res.required.push(param.clone());
+            }
+            Exp::List(optional) if optional.len() == 2 => {
+                let name = optional.nth(0)?;
+                name.as_atom()?.as_identifier()?;
+                check_bindable(name)?;
+                res.optional.push((name.clone(), optional.nth(1)?.clone()));
+            }
+            _ => return Err(LispicoError::type_mismatch("a parameter", param)),
+        }
+    }
+    Ok(res)
+}
+
+impl Params {
+    pub fn arity(&self) -> Arity {
+        let min = self.required.len();
+        match (self.optional.len(), &self.rest) {
+            (_, Some(_)) => Arity::AtLeast(min),
+            (0, None) => Arity::Exactly(min),
+            (optional, None) => Arity::Between(min, min + optional),
+        }
+    }
+
+    // Expects as many arguments as the arity allows. Defaults are evaluated in order, seeing the
+    // parameters bound before them.
+    fn bind(&self, args: &[Exp], env: List) -> Result<List> {
+        let mut env = env;
+        let mut args = args.iter();
+        for name in &self.required {
+            env = env_bind(name.clone(), args.next().unwrap().clone(), env);
+        }
+        for (name, default) in &self.optional {
+            let value = match args.next() {
+                Some(value) => value.clone(),
+                None => default.eval(env.clone())?.0,
+            };
+            env = env_bind(name.clone(), value, env);
+        }
+        if let Some(name) = &self.rest {
+            let rest = args.cloned().collect();
+            env = env_bind(name.clone(), Exp::List(rest), env);
+        }
+        Ok(env)
+    }
+}
+
+pub fn check_bindable(name: &Exp) -> Result<()> {
+    match name.unlocated() {
+        Exp::Atom(Atom::Identifier(id)) if id == TRUE => Err(LispicoError::Custom(format!(
+            "Cannot rebind the constant {TRUE}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub fn env_bind(name: Exp, value: Exp, env: List) -> List {
+    let new_binding = List::Cons(
+        Rc::new(name),
+        Rc::new(List::Cons(Rc::new(value), Rc::new(List::Nil))),
+    );
+    List::Cons(Rc::new(Exp::List(new_binding)), Rc::new(env))
+}
+
+// Binds a group of recursive definitions, so that each function in the group sees every name in it.
+pub fn bind_group(group: &Rc<Vec<(Exp, Exp)>>, env: List) -> List {
+    let mut env = env;
+    for (name, value) in group.iter() {
+        let value = match value {
+            Exp::Closure(closure) => Exp::Closure(Rc::new(Closure {
+                group: Some(group.clone()),
+                ..Closure::clone(closure)
+            })),
+            Exp::Macro(transformer) => Exp::Macro(Rc::new(Closure {
+                group: Some(group.clone()),
+                ..Closure::clone(transformer)
+            })),
+            Exp::Operative(operative, env_param) => Exp::Operative(
+                Rc::new(Closure {
+                    group: Some(group.clone()),
+                    ..Closure::clone(operative)
+                }),
+                env_param.clone(),
+            ),
+            _ => value.clone(),
+        };
+        env = env_bind(name.clone(), value, env);
+    }
+    env
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -193,13 +965,13 @@ mod tests {
                 .unwrap();
             let env_exp = construct_exp(pair);
             let env;
-            if let Exp::List(list) = env_exp {
-                env = list;
+            if let Exp::List(list) = env_exp.unlocated() {
+                env = list.clone();
             } else {
                 panic!("Expected a list, but got an atom");
             }
 
-            let res = env_lookup(identifier, &env);
+            let res = env_lookup(identifier, &env).unwrap();
             assert_eq!(res, expected, "env: {env_str}");
         }
     }
@@ -207,10 +979,10 @@ mod tests {
     #[test]
     fn list_methods() -> Result<()> {
         let list = List::Cons(
-            Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-            Box::new(List::Cons(
-                Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                Box::new(List::Nil),
+            Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+            Rc::new(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                Rc::new(List::Nil),
             )),
         );
         assert_eq!(list.len(), 2);
@@ -233,28 +1005,28 @@ mod tests {
     #[test]
     fn list_extend() {
         let list1 = List::Cons(
-            Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-            Box::new(List::Cons(
-                Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                Box::new(List::Nil),
+            Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+            Rc::new(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                Rc::new(List::Nil),
             )),
         );
         let list2 = List::Cons(
-            Box::new(Exp::Atom(Atom::Identifier("c".to_string()))),
-            Box::new(List::Cons(
-                Box::new(Exp::Atom(Atom::Identifier("d".to_string()))),
-                Box::new(List::Nil),
+            Rc::new(Exp::Atom(Atom::Identifier("c".to_string()))),
+            Rc::new(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("d".to_string()))),
+                Rc::new(List::Nil),
             )),
         );
         let expected = List::Cons(
-            Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-            Box::new(List::Cons(
-                Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                Box::new(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("c".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("d".to_string()))),
-                        Box::new(List::Nil),
+            Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+            Rc::new(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("c".to_string()))),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("d".to_string()))),
+                        Rc::new(List::Nil),
                     )),
                 )),
             )),
@@ -266,73 +1038,3 @@ mod tests {
         assert_eq!(List::Nil.extend(&List::Nil), List::Nil);
     }
 }
-
-impl Exp {
-    pub fn eval(&self, env: List) -> Result<(Exp, List)> {
-        match self {
-            Exp::Atom(atom) => atom.eval(env),
-            Exp::List(list) => list.eval(env),
-        }
-    }
-}
-
-impl Display for Exp {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Exp::Atom(atom) => write!(f, "{atom}"),
-            Exp::List(List::Cons(hd, tl)) if matches!(**hd, Exp::Atom(Atom::Identifier(ref id)) if id == "'") =>
-            {
-                write!(f, "'{tl}")
-            }
-            Exp::List(list) => write!(f, "({list})"),
-        }
-    }
-}
-
-fn eval_function(operator: &Exp, args: &List, env: List) -> Result<(Exp, List)> {
-    let (operator, env) = operator.eval(env)?;
-
-    match operator {
-        Exp::List(list) => {
-            if list.nth(0)?.as_atom()?.as_identifier()? != "->" {
-                return Err("Expected an arrow operator".into());
-            }
-
-            let param_names = list.nth(1)?.as_list()?;
-            let inner_env = bind_params(param_names, args, env.clone())?;
-            let body = list.nth(2)?;
-
-            let (res, _) = body.eval(inner_env)?;
-
-            return Ok((res, env));
-        }
-        Exp::Atom(Atom::Identifier(identifier)) => {
-            for (op, func) in PREDEFINED_OPERATORS.iter() {
-                if *op == identifier {
-                    return func(args, env);
-                }
-            }
-            return Err("Unknown operator".into());
-        }
-        _ => Err("Expected an identifier, but got a list".into()),
-    }
-}
-
-fn bind_params(param_names: &List, args: &List, env: List) -> Result<List> {
-    if let List::Nil = *param_names {
-        return Ok(env);
-    }
-    debug_assert!(param_names.hd().is_ok());
-    let current_param_name = param_names.hd().unwrap().clone();
-    let (current_param_value, _) = args.hd()?.eval(env.clone())?;
-    let new_binding = List::Cons(
-        Box::new(current_param_name),
-        Box::new(List::Cons(
-            Box::new(current_param_value),
-            Box::new(List::Nil),
-        )),
-    );
-    let new_env = List::Cons(Box::new(Exp::List(new_binding)), Box::new(env));
-
-    bind_params(param_names.tl()?, args.tl()?, new_env)
-}
@@ -0,0 +1,78 @@
+use std::io::BufRead;
+
+use std::rc::Rc;
+
+use crate::exp::{env_bind, Atom, Exp, HostOperator, LispicoError, List, Result};
+use crate::modules::{execute_file, execute_stream};
+use crate::parser;
+use crate::predefined::get_default_env;
+
+pub struct Interpreter {
+    env: List,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::with_env(get_default_env())
+    }
+
+    pub fn with_env(env: List) -> Self {
+        Interpreter { env }
+    }
+
+    pub fn bindings(&self) -> &List {
+        &self.env
+    }
+
+    pub fn register_operator(
+        &mut self,
+        name: &str,
+        func: impl Fn(&List, List) -> Result<(Exp, List)> + 'static,
+    ) {
+        let operator = HostOperator {
+            name: name.to_string(),
+            func: Box::new(func),
+        };
+        self.env = env_bind(
+            Exp::Atom(Atom::Identifier(name.to_string())),
+            Exp::Host(Rc::new(operator)),
+            self.env.clone(),
+        );
+    }
+
+    pub fn eval_exp(&mut self, exp: &Exp) -> Result<Exp> {
+        let (res, new_env) = exp.eval(self.env.clone())?;
+        self.env = new_env;
+        Ok(res)
+    }
+
+    pub fn eval_str(&mut self, input: &str) -> Result<Exp> {
+        let mut res = Exp::List(List::Nil);
+        for exp in parser::parse_program(input)? {
+            res = self.eval_exp(&exp)?;
+        }
+        Ok(res)
+    }
+
+    pub fn load_file(&mut self, path: &str) -> Result<()> {
+        self.env = execute_file(path, self.env.clone()).map_err(|e| match e {
+            LispicoError::Io(source) => LispicoError::ReadFailed {
+                path: path.to_string(),
+                source,
+            },
+            e => e,
+        })?;
+        Ok(())
+    }
+
+    pub fn repl(&mut self, stream: impl BufRead) -> Result<()> {
+        self.env = execute_stream(stream, "<stdin>", self.env.clone(), true)?;
+        Ok(())
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
@@ -0,0 +1,13 @@
+pub mod error;
+pub mod exp;
+mod interpreter;
+pub mod modules;
+pub mod parser;
+pub mod predefined;
+#[cfg(test)]
+mod tests;
+
+pub use error::{Arity, LispicoError, Result};
+pub use exp::{Atom, Exp, List};
+pub use interpreter::Interpreter;
+pub use predefined::get_default_env;
@@ -1,27 +1,28 @@
 use std::env;
 use std::io;
+use std::process::ExitCode;
 
-mod exp;
-mod modules;
-mod parser;
-mod predefined;
-mod tests;
+use lispico::Interpreter;
 
-use predefined::get_default_env;
-
-fn main() -> io::Result<()> {
+fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
+    let mut interpreter = Interpreter::new();
 
-    if args.len() > 2 {
-        println!("Error - Too many arguments");
-        println!("Usage: lispico [file]");
+    let res = if args.len() > 2 {
+        eprintln!("Error - Too many arguments");
+        eprintln!("Usage: lispico [file]");
+        return ExitCode::from(2);
     } else if args.len() == 2 {
-        let path = &args[1];
-        modules::execute_file(path, get_default_env()).expect("failed to execute file");
+        interpreter.load_file(&args[1])
     } else {
-        modules::execute_stream(io::stdin().lock(), get_default_env(), true)
-            .expect("failed to execute stream");
-    }
+        interpreter.repl(io::stdin().lock())
+    };
 
-    return Ok(());
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e.traceback());
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -4,48 +4,135 @@ use std::io::{self, BufRead, BufReader, Write};
 use crate::exp::*;
 use crate::parser;
 
-use crate::parser::LispicoParser;
-use pest::Parser;
-
 pub fn execute_file(path: &str, env: List) -> Result<List> {
     let f = File::open(path)?;
     let f = BufReader::new(f);
-    execute_stream(f, env, false)
+    execute_stream(f, path, env, false)
 }
 
-pub fn execute_stream(stream: impl BufRead, env: List, prompt: bool) -> Result<List> {
+pub fn execute_stream(stream: impl BufRead, source: &str, env: List, prompt: bool) -> Result<List> {
     let mut env = env;
-    let mut lines = stream.lines();
+    let mut lines = stream.lines().enumerate();
+    let mut pending = String::new();
+    let mut start = Location {
+        file: source.into(),
+        line: 0,
+        column: 0,
+    };
     loop {
         if prompt {
-            print!("$ ");
+            print!("{}", if pending.is_empty() { "$ " } else { "> " });
             io::stdout().flush().unwrap();
         }
 
-        let line = match lines.next() {
+        let (line_number, line) = match lines.next() {
             None => break,
-            Some(Ok(line)) => line,
-            Some(Err(e)) => return Err(e.into()),
+            Some((i, Ok(line))) => (i + 1, line),
+            Some((_, Err(e))) => return Err(e.into()),
         };
 
-        if line.is_empty() {
+        if pending.is_empty() {
+            match line.find(|c: char| !c.is_whitespace()) {
+                None => continue,
+                Some(column) => {
+                    start.line = line_number;
+                    start.column = column + 1;
+                }
+            }
+        }
+        pending.push_str(&line);
+        pending.push('\n');
+        if !is_complete(&pending) {
             continue;
         }
+        let input = std::mem::take(&mut pending);
 
-        let pair = LispicoParser::parse(parser::Rule::program, line.as_str())
-            .expect("failed to parse input")
-            .next()
-            .unwrap();
+        let exps = match parser::parse_source(&input, source, start.line) {
+            Ok(exps) => exps,
+            Err(e) if prompt => {
+                eprintln!("{}", e.traceback());
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
 
-        let exp = parser::construct_exp(pair);
-        let (res, new_env) = exp.eval(env).unwrap();
-        env = new_env;
+        for exp in exps {
+            let (res, new_env) = match exp.eval(env.clone()) {
+                Ok(res) => res,
+                // An interactive session reports the error and keeps the environment it had so far.
+                Err(e) if prompt => {
+                    eprintln!("{}", e.traceback());
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            env = new_env;
 
-        if let Exp::List(List::Nil) = res {
-            continue;
+            if let Exp::List(List::Nil) = res {
+                continue;
+            }
+            println!("{res}");
         }
-        println!("{res}");
+    }
+
+    if !pending.is_empty() {
+        let e = LispicoError::UnterminatedExpression(start);
+        if !prompt {
+            return Err(e);
+        }
+        eprintln!("{}", e.traceback());
     }
 
     Ok(env)
 }
+
+// Whether the input closes every parenthesis, string and block comment it opens.
+fn is_complete(input: &str) -> bool {
+    let mut depth = 0;
+    let mut comment_depth = 0;
+    let mut in_string = false;
+    let mut in_raw_string = false;
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        let mut len = c.len_utf8();
+        if in_raw_string {
+            if rest.starts_with(r#"""""#) {
+                len = 3;
+                in_raw_string = false;
+            }
+        } else if in_string {
+            match c {
+                '\\' => len += rest[1..].chars().next().map_or(0, char::len_utf8),
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if comment_depth > 0 {
+            if rest.starts_with(";|") {
+                len = 2;
+                comment_depth += 1;
+            } else if rest.starts_with("|;") {
+                len = 2;
+                comment_depth -= 1;
+            }
+        } else if rest.starts_with(r#"""""#) {
+            len = 3;
+            in_raw_string = true;
+        } else if rest.starts_with(";|") {
+            len = 2;
+            comment_depth = 1;
+        } else if rest.starts_with(";_") && parser::starts_datum(&rest[2..]) {
+            // The datum following an expression comment is read like any other.
+            len = 2;
+        } else {
+            match c {
+                '"' => in_string = true,
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ';' => len = rest.find('\n').unwrap_or(rest.len()),
+                _ => {}
+            }
+        }
+        rest = &rest[len..];
+    }
+    depth <= 0 && !in_string && !in_raw_string && comment_depth == 0
+}
@@ -1,4 +1,9 @@
+use std::rc::Rc;
+
+use num_rational::BigRational;
+use pest::error::{Error, LineColLocation};
 use pest::iterators::Pair;
+use pest::Parser;
 use pest_derive::Parser;
 
 use crate::exp::*;
@@ -7,34 +12,231 @@ use crate::exp::*;
 #[grammar = "lispico.pest"]
 pub struct LispicoParser;
 
-fn construct_list(pair: Pair<Rule>) -> List {
+struct Source {
+    file: Rc<str>,
+    first_line: usize,
+}
+
+impl Source {
+    fn locate(&self, pair: &Pair<Rule>, exp: Exp) -> Exp {
+        if let Exp::List(List::Nil) = exp {
+            return exp;
+        }
+        let (line, column) = pair.line_col();
+        let location = Location {
+            file: self.file.clone(),
+            line: line + self.first_line.saturating_sub(1),
+            column,
+        };
+        Exp::Located(Box::new(exp), Rc::new(location))
+    }
+}
+
+fn construct_list(pair: Pair<Rule>, source: &Source) -> List {
     match pair.as_rule() {
         Rule::nil => List::Nil,
         Rule::list => {
             let mut pairs = pair.into_inner();
             List::Cons(
-                Box::new(construct_exp(pairs.next().unwrap())),
-                Box::new(construct_list(pairs.next().unwrap())),
+                Rc::new(construct_source_exp(pairs.next().unwrap(), source)),
+                Rc::new(construct_list(pairs.next().unwrap(), source)),
             )
         }
         _ => unreachable!("unexpected rule: {:?}", pair.as_rule()),
     }
 }
 
-pub fn construct_exp(pair: Pair<Rule>) -> Exp {
+fn construct_source_exp(pair: Pair<Rule>, source: &Source) -> Exp {
     match pair.as_rule() {
         Rule::nil => Exp::List(List::Nil),
         Rule::identifier => Exp::Atom(Atom::Identifier(pair.as_str().to_string())),
-        Rule::number => Exp::Atom(Atom::Number(pair.as_str().parse().unwrap())),
-        Rule::string => Exp::Atom(Atom::String(pair.as_str().to_string())),
-        Rule::list => Exp::List(construct_list(pair)),
-        Rule::quote_exp => Exp::List(List::Cons(
-            Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-            Box::new(List::Cons(
-                Box::new(construct_exp(pair.into_inner().next().unwrap())),
-                Box::new(List::Nil),
-            )),
-        )),
+        Rule::number => Exp::Atom(construct_number(pair.as_str())),
+        Rule::quoted_string => construct_source_exp(pair.into_inner().next().unwrap(), source),
+        Rule::string => Exp::Atom(Atom::String(unescape(pair.as_str()))),
+        Rule::raw_string => {
+            // A text block starting on the line after its opening quotes does not include that newline.
+            let text = pair.as_str();
+            let text = text.strip_prefix('\n').unwrap_or(text);
+            Exp::Atom(Atom::String(text.to_string()))
+        }
+        Rule::list => Exp::List(construct_list(pair, source)),
+        Rule::paren_list => {
+            let inner = pair.clone().into_inner().next().unwrap();
+            source.locate(&pair, construct_source_exp(inner, source))
+        }
+        Rule::quote_exp => {
+            let quote = Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(construct_source_exp(
+                        pair.clone().into_inner().next().unwrap(),
+                        source,
+                    )),
+                    Rc::new(List::Nil),
+                )),
+            ));
+            source.locate(&pair, quote)
+        }
+        Rule::quasiquote_exp => {
+            let template = construct_template(pair.clone().into_inner().next().unwrap(), source);
+            source.locate(&pair, template)
+        }
         _ => unreachable!("unexpected rule: {:?}", pair.as_rule()),
     }
 }
+
+// Expands a template into the form constructing it: quoted data, the unquoted forms, and a call to
+// `` ` `` for each list, with spliced forms wrapped in a call to `,@`.
+fn construct_template(pair: Pair<Rule>, source: &Source) -> Exp {
+    match pair.as_rule() {
+        Rule::unquote => construct_source_exp(pair.into_inner().next().unwrap(), source),
+        Rule::template_quote => {
+            let quoted = construct_template(pair.into_inner().next().unwrap(), source);
+            construct_call("`", vec![quote(identifier("'")), quoted])
+        }
+        Rule::template_list => {
+            let mut items = Vec::new();
+            let mut inner = pair.clone().into_inner().next().unwrap();
+            while inner.as_rule() == Rule::template_items {
+                let mut pairs = inner.into_inner();
+                let item = pairs.next().unwrap();
+                items.push(match item.as_rule() {
+                    Rule::unquote_splicing => construct_call(
+                        ",@",
+                        vec![construct_source_exp(
+                            item.into_inner().next().unwrap(),
+                            source,
+                        )],
+                    ),
+                    _ => construct_template(item, source),
+                });
+                inner = pairs.next().unwrap();
+            }
+            if items.is_empty() {
+                return Exp::List(List::Nil);
+            }
+            source.locate(&pair, construct_call("`", items))
+        }
+        // A nested template is data, whose unquoted forms belong to it rather than to this one.
+        _ => quote(construct_source_exp(pair, source)),
+    }
+}
+
+fn construct_call(operator: &str, args: Vec<Exp>) -> Exp {
+    Exp::List(List::Cons(
+        Rc::new(identifier(operator)),
+        Rc::new(args.into_iter().collect()),
+    ))
+}
+
+fn quote(exp: Exp) -> Exp {
+    construct_call("'", vec![exp])
+}
+
+fn identifier(name: &str) -> Exp {
+    Exp::Atom(Atom::Identifier(name.to_string()))
+}
+
+fn construct_number(text: &str) -> Atom {
+    match text {
+        "+inf.0" => return Atom::Number(f64::INFINITY),
+        "-inf.0" => return Atom::Number(f64::NEG_INFINITY),
+        "+nan.0" | "-nan.0" => return Atom::Number(f64::NAN),
+        _ if text.contains(['.', 'e']) => return Atom::Number(text.parse().unwrap()),
+        _ => {}
+    }
+    let num = match text.split_once('/') {
+        Some((numerator, denominator)) => {
+            BigRational::new(numerator.parse().unwrap(), denominator.parse().unwrap())
+        }
+        None => BigRational::from_integer(text.parse().unwrap()),
+    };
+    Atom::exact(num)
+}
+
+// Reads a number written as in source, or `None` if the whole text is not one.
+pub fn parse_number(text: &str) -> Option<Atom> {
+    let pair = LispicoParser::parse(Rule::number, text).ok()?.next()?;
+    if pair.as_str().len() != text.len() {
+        return None;
+    }
+    Some(construct_number(text))
+}
+
+// Whether `text` starts with a datum, so that `;_` just before it starts an expression comment.
+pub fn starts_datum(text: &str) -> bool {
+    LispicoParser::parse(Rule::datum_start, text).is_ok()
+}
+
+// Expects the escapes accepted by the `string` rule.
+fn unescape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some('r') => res.push('\r'),
+            Some('0') => res.push('\0'),
+            Some('u') => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                let code = u32::from_str_radix(&code, 16).unwrap();
+                res.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            Some(c) => res.push(c),
+            None => unreachable!("dangling escape in string: {s}"),
+        }
+    }
+    res
+}
+
+pub fn construct_exp(pair: Pair<Rule>) -> Exp {
+    let source = Source {
+        file: "<input>".into(),
+        first_line: 1,
+    };
+    construct_source_exp(pair, &source)
+}
+
+pub fn parse(input: &str) -> Result<Exp> {
+    let pair = LispicoParser::parse(Rule::expression, input)?
+        .next()
+        .unwrap();
+    Ok(construct_exp(pair))
+}
+
+pub fn parse_program(input: &str) -> Result<Vec<Exp>> {
+    parse_source(input, "<input>", 1)
+}
+
+// Lines are numbered from `first_line`, where 0 is taken as 1 for the first line.
+pub fn parse_source(input: &str, file: &str, first_line: usize) -> Result<Vec<Exp>> {
+    let pairs = LispicoParser::parse(Rule::program, input)
+        .map_err(|e| relocate_error(e, file, first_line))?;
+    let source = Source {
+        file: file.into(),
+        first_line,
+    };
+    Ok(pairs
+        .map(|pair| construct_source_exp(pair, &source))
+        .collect())
+}
+
+fn relocate_error(e: Error<Rule>, file: &str, first_line: usize) -> Error<Rule> {
+    let mut e = e.with_path(file);
+    let offset = first_line.saturating_sub(1);
+    e.line_col = match e.line_col {
+        LineColLocation::Pos((line, column)) => LineColLocation::Pos((line + offset, column)),
+        LineColLocation::Span((start_line, start_column), (end_line, end_column)) => {
+            LineColLocation::Span(
+                (start_line + offset, start_column),
+                (end_line + offset, end_column),
+            )
+        }
+    };
+    e
+}
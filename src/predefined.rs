@@ -1,88 +1,154 @@
-use crate::exp::{Atom, Exp, List, Result};
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::exp::{
+    bind_group, check_bindable, macroexpand_1, parse_params, Arity, Atom, Closure, Exp,
+    LispicoError, List, Result, Step,
+};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
 use crate::modules::execute_file;
+use crate::parser::{parse, parse_number};
 
 type LispicoOperator = fn(&List, List) -> Result<(Exp, List)>;
+// An operator that may leave one of its forms to be evaluated in tail position.
+type TailOperator = fn(&List, List) -> Result<Step>;
+
+thread_local! {
+    // Numbers the identifiers made by `gensym`.
+    static GENSYM_COUNTER: Cell<usize> = const { Cell::new(0) };
+}
 
-pub static PREDEFINED_OPERATORS: [(&str, LispicoOperator); 15] = [
+pub static PREDEFINED_OPERATORS: [(&str, LispicoOperator); 48] = [
     (".", |args, env| -> Result<(Exp, List)> {
         let (new_hd, new_env) = args.hd()?.eval(env)?;
         let (new_tl, new_env) = args.tl()?.hd()?.eval(new_env)?;
-        if let Exp::List(list) = new_tl {
-            return Ok((
-                Exp::List(List::Cons(Box::new(new_hd), Box::new(list))),
-                new_env,
-            ));
-        }
-        return Err("Expected a list, but got an atom".into());
+        let list = new_tl.as_list()?.clone();
+        Ok((
+            Exp::List(List::Cons(Rc::new(new_hd), Rc::new(list))),
+            new_env,
+        ))
     }),
     (".<", |args, env| -> Result<(Exp, List)> {
         let (arg, new_env) = args.hd()?.eval(env)?;
-        if let Exp::List(list) = arg {
-            return Ok((list.hd()?.clone(), new_env));
-        }
-        return Err("Expected a list, but got an atom".into());
+        Ok((arg.as_list()?.hd()?.clone(), new_env))
     }),
     (".>", |args, env| -> Result<(Exp, List)> {
         let (arg, new_env) = args.hd()?.eval(env)?;
-        if let Exp::List(list) = arg {
-            return Ok((Exp::List(list.tl()?.clone()), new_env));
-        }
-        panic!("Expected a list, but got an atom");
+        Ok((Exp::List(arg.as_list()?.tl()?.clone()), new_env))
     }),
     ("'", |args, env| -> Result<(Exp, List)> {
-        Ok((args.hd()?.clone(), env))
-    }),
-    ("=", |args, env| -> Result<(Exp, List)> {
-        let (lhs, env) = args.nth(0)?.eval(env)?;
-        let (rhs, env) = args.nth(1)?.eval(env)?;
-        if lhs != rhs {
-            return Ok((Exp::List(List::Nil), env));
-        } else {
-            return Ok((Exp::Atom(Atom::Identifier("t".to_string())), env));
-        }
+        Ok((args.hd()?.strip_locations(), env))
     }),
-    ("?", |args, env| -> Result<(Exp, List)> {
-        let (cond, new_env) = args.hd()?.eval(env)?;
-        match cond {
-            Exp::List(List::Nil) if matches!(args.tl()?.tl()?, List::Nil) => {
-                Ok((Exp::List(List::Nil), new_env))
+    // Builds the list of a quasiquoted template, splicing in the items of `(,@ form)` arguments.
+    ("`", |args, env| -> Result<(Exp, List)> {
+        let mut items = Vec::new();
+        let mut env = env;
+        for arg in args.iter() {
+            match arg.unlocated() {
+                Exp::List(List::Cons(hd, spliced)) if matches!(hd.unlocated(), Exp::Atom(Atom::Identifier(id)) if id == ",@") =>
+                {
+                    let (value, new_env) = spliced.hd()?.eval(env)?;
+                    items.extend(value.as_list()?.iter().cloned());
+                    env = new_env;
+                }
+                _ => {
+                    let (value, new_env) = arg.eval(env)?;
+                    items.push(value);
+                    env = new_env;
+                }
             }
-            Exp::List(List::Nil) => args.tl()?.tl()?.hd()?.eval(new_env),
-            _ => args.tl()?.hd()?.eval(new_env),
         }
+        Ok((Exp::List(items.into_iter().collect()), env))
     }),
+    ("=", |args, env| eval_comparison_operator("=", args, env)),
     (":=", |args, env| -> Result<(Exp, List)> {
         let name = args.hd()?;
-        if let Exp::List(_) = name {
-            return Err("Expected an identifier, but got a list".into());
+        if let Exp::List(_) = name.unlocated() {
+            return Err(LispicoError::type_mismatch("an identifier", name));
+        }
+        check_bindable(name)?;
+        let value_form = args.tl()?.hd()?;
+        let (value, new_env) = value_form.eval(env)?;
+        // A function defined in place may call itself, as if defined with `::=`.
+        if is_function_form(value_form) {
+            let name = Exp::Atom(Atom::Identifier(name.to_string()));
+            let group = Rc::new(vec![(name, value)]);
+            return Ok((Exp::List(List::Nil), bind_group(&group, new_env)));
         }
-        let (value, new_env) = args.tl()?.hd()?.eval(env)?;
         let new_binding = List::Cons(
-            Box::new(Exp::Atom(Atom::Identifier(name.to_string()))),
-            Box::new(List::Cons(Box::new(value), Box::new(List::Nil))),
+            Rc::new(Exp::Atom(Atom::Identifier(name.to_string()))),
+            Rc::new(List::Cons(Rc::new(value), Rc::new(List::Nil))),
         );
-        let new_env = List::Cons(Box::new(Exp::List(new_binding)), Box::new(new_env));
+        let new_env = List::Cons(Rc::new(Exp::List(new_binding)), Rc::new(new_env));
         Ok((Exp::List(List::Nil), new_env))
     }),
-    ("{}", |args, env| -> Result<(Exp, List)> {
-        let inner_env = construct_let_env(args.nth(0)?.as_list()?, env.clone())?;
-        let body = args.nth(1)?;
-        let (res, _) = body.eval(inner_env)?;
-        Ok((res, env))
-    }),
     ("->", |args, env| -> Result<(Exp, List)> {
-        Ok((
-            Exp::List(List::Cons(
-                Box::new(Exp::Atom(Atom::Identifier("->".to_string()))),
-                Box::new(args.clone()),
-            )),
-            env,
-        ))
+        let closure = construct_closure(args.nth(0)?, args.tl()?, &env)?;
+        Ok((Exp::Closure(Rc::new(closure)), env))
+    }),
+    ("macro", |args, env| -> Result<(Exp, List)> {
+        let transformer = construct_closure(args.nth(0)?, args.tl()?, &env)?;
+        Ok((Exp::Macro(Rc::new(transformer)), env))
+    }),
+    ("operative", |args, env| -> Result<(Exp, List)> {
+        let env_param = args.nth(1)?;
+        check_bindable(env_param)?;
+        let env_param = env_param.as_atom()?.as_identifier()?.into();
+        let operative = construct_closure(args.nth(0)?, args.tl()?.tl()?, &env)?;
+        Ok((Exp::Operative(Rc::new(operative), env_param), env))
+    }),
+    ("macroexpand-1", |args, env| -> Result<(Exp, List)> {
+        let (form, env) = args.hd()?.eval(env)?;
+        let expansion = macroexpand_1(&form, &env)?;
+        Ok((expansion.unwrap_or(form), env))
+    }),
+    ("macroexpand", |args, env| -> Result<(Exp, List)> {
+        let (mut form, env) = args.hd()?.eval(env)?;
+        while let Some(expansion) = macroexpand_1(&form, &env)? {
+            form = expansion;
+        }
+        Ok((form, env))
+    }),
+    ("gensym", |args, env| -> Result<(Exp, List)> {
+        let (prefix, env) = match args {
+            List::Nil => ("g".to_string(), env),
+            _ => {
+                let (prefix, env) = args.hd()?.eval(env)?;
+                (prefix.as_atom()?.as_string()?.to_string(), env)
+            }
+        };
+        let n = GENSYM_COUNTER.with(|counter| {
+            counter.set(counter.get() + 1);
+            counter.get()
+        });
+        // `~` is not an identifier character, so no name written in source can clash with these.
+        Ok((Exp::Atom(Atom::Identifier(format!("{prefix}~{n}"))), env))
+    }),
+    ("read-string", |args, env| -> Result<(Exp, List)> {
+        let (text, env) = args.hd()?.eval(env)?;
+        let exp = parse(text.as_atom()?.as_string()?)?;
+        Ok((exp.strip_locations(), env))
+    }),
+    ("::=", |args, env| -> Result<(Exp, List)> {
+        let mut definitions = Vec::new();
+        let mut rest = args;
+        while let List::Cons(name, tl) = rest {
+            definitions.push((name.as_ref(), tl.hd()?));
+            rest = tl.tl()?;
+        }
+        let group = eval_group(definitions, &env)?;
+        Ok((Exp::List(List::Nil), bind_group(&group, env)))
     }),
     ("#", |args, env| -> Result<(Exp, List)> {
         let (filename, env) = args.hd()?.eval(env)?;
         let path = filename.as_atom()?.as_string()?;
-        let new_env = execute_file(path, env)?;
+        let new_env = execute_file(path, env).map_err(|e| LispicoError::ImportFailed {
+            path: path.to_string(),
+            source: Box::new(e),
+        })?;
         Ok((Exp::List(List::Nil), new_env))
     }),
     ("+", |args, env| eval_numeric_operator("+", args, env)),
@@ -90,59 +156,703 @@ pub static PREDEFINED_OPERATORS: [(&str, LispicoOperator); 15] = [
     ("*", |args, env| eval_numeric_operator("*", args, env)),
     ("/", |args, env| eval_numeric_operator("/", args, env)),
     ("^", |args, env| eval_numeric_operator("^", args, env)),
+    ("//", |args, env| eval_numeric_operator("//", args, env)),
+    ("%", |args, env| eval_numeric_operator("%", args, env)),
+    ("%%", |args, env| eval_numeric_operator("%%", args, env)),
+    ("<", |args, env| eval_comparison_operator("<", args, env)),
+    (">", |args, env| eval_comparison_operator(">", args, env)),
+    ("<=", |args, env| eval_comparison_operator("<=", args, env)),
+    (">=", |args, env| eval_comparison_operator(">=", args, env)),
+    ("!=", |args, env| eval_comparison_operator("!=", args, env)),
+    ("int", |args, env| -> Result<(Exp, List)> {
+        let (value, env) = args.hd()?.eval(env)?;
+        let atom = value.as_atom()?;
+        let num = match atom {
+            Atom::Number(num) => BigInt::from_f64(num.trunc()).ok_or_else(|| {
+                LispicoError::Custom(format!("Cannot convert {num:?} to an integer"))
+            })?,
+            _ => match atom.as_exact() {
+                Some(num) => num.trunc().to_integer(),
+                None => return Err(LispicoError::type_mismatch("a number", &value)),
+            },
+        };
+        Ok((Exp::Atom(Atom::exact(BigRational::from_integer(num))), env))
+    }),
+    ("not", |args, env| -> Result<(Exp, List)> {
+        let (value, env) = args.hd()?.eval(env)?;
+        Ok((Exp::truth(!value.is_true()), env))
+    }),
+    ("float", |args, env| -> Result<(Exp, List)> {
+        let (value, env) = args.hd()?.eval(env)?;
+        let num = value.as_atom()?.as_number()?;
+        Ok((Exp::Atom(Atom::Number(num)), env))
+    }),
+    ("string-concat", |args, env| {
+        eval_string_operator("string-concat", args, env)
+    }),
+    ("string-length", |args, env| {
+        eval_string_operator("string-length", args, env)
+    }),
+    ("substring", |args, env| {
+        eval_string_operator("substring", args, env)
+    }),
+    ("string-index", |args, env| {
+        eval_string_operator("string-index", args, env)
+    }),
+    ("string-split", |args, env| {
+        eval_string_operator("string-split", args, env)
+    }),
+    ("string-join", |args, env| {
+        eval_string_operator("string-join", args, env)
+    }),
+    ("string-trim", |args, env| {
+        eval_string_operator("string-trim", args, env)
+    }),
+    ("string-upper", |args, env| {
+        eval_string_operator("string-upper", args, env)
+    }),
+    ("string-lower", |args, env| {
+        eval_string_operator("string-lower", args, env)
+    }),
+    ("string-replace", |args, env| {
+        eval_string_operator("string-replace", args, env)
+    }),
+    ("string-starts-with", |args, env| {
+        eval_string_operator("string-starts-with", args, env)
+    }),
+    ("string-ends-with", |args, env| {
+        eval_string_operator("string-ends-with", args, env)
+    }),
+    ("string->number", |args, env| {
+        eval_string_operator("string->number", args, env)
+    }),
+    ("number->string", |args, env| {
+        eval_string_operator("number->string", args, env)
+    }),
+    ("symbol->string", |args, env| {
+        eval_string_operator("symbol->string", args, env)
+    }),
+    ("string->symbol", |args, env| {
+        eval_string_operator("string->symbol", args, env)
+    }),
+];
+
+pub static TAIL_OPERATORS: [(&str, TailOperator); 8] = [
+    ("?", |args, env| -> Result<Step> {
+        let (cond, new_env) = args.hd()?.eval(env)?;
+        let branch = match cond {
+            Exp::List(List::Nil) => match args.tl()?.tl()? {
+                List::Nil => return Ok(Step::Done(Exp::List(List::Nil), new_env)),
+                otherwise => otherwise.hd()?,
+            },
+            _ => args.tl()?.hd()?,
+        };
+        Ok(Step::Tail {
+            exp: branch.clone(),
+            env: new_env,
+            scope: None,
+        })
+    }),
+    ("do", eval_sequence),
+    // Evaluates a value as a form, in the current environment or in a given one.
+    ("eval", |args, env| -> Result<Step> {
+        let (form, env) = args.hd()?.eval(env)?;
+        match args.tl()? {
+            List::Nil => Ok(Step::Tail {
+                exp: form,
+                env,
+                scope: None,
+            }),
+            rest => {
+                let (form_env, env) = rest.hd()?.eval(env)?;
+                Ok(Step::Tail {
+                    exp: form,
+                    env: form_env.as_list()?.clone(),
+                    scope: Some(env),
+                })
+            }
+        }
+    }),
+    // Calls a function or an operator with a list of values. Forms that do not evaluate their
+    // arguments have no use for values, so macros, operatives and special forms are rejected.
+    ("apply", |args, env| -> Result<Step> {
+        let (function, env) = args.nth(0)?.eval(env)?;
+        let (values, env) = args.nth(1)?.eval(env)?;
+        let values: Vec<Exp> = values.as_list()?.iter().cloned().collect();
+        match &function {
+            Exp::Closure(closure) => {
+                let inner_env = closure.bind_args(&values, || function.to_string())?;
+                Ok(eval_sequence(&closure.body, inner_env)?.in_scope(env))
+            }
+            // Builtins and host operators evaluate their arguments, so each value is passed quoted
+            // to evaluate to itself.
+            Exp::Host(operator) => {
+                let (res, env) = (operator.func)(&quote_values(values), env)?;
+                Ok(Step::Done(res, env))
+            }
+            Exp::Atom(Atom::Identifier(name)) if !SPECIAL_FORMS.contains(&name.as_str()) => {
+                operator_step(name, &quote_values(values), env)
+            }
+            Exp::Macro(_) | Exp::Operative(..) | Exp::Atom(Atom::Identifier(_)) => {
+                Err(LispicoError::Custom(format!(
+                    "Cannot apply {function}, which takes forms rather than values"
+                )))
+            }
+            _ => Err(LispicoError::type_mismatch("a function", &function)),
+        }
+    }),
+    ("and", |args, env| eval_logical_operator(false, args, env)),
+    ("or", |args, env| eval_logical_operator(true, args, env)),
+    ("{}", |args, env| -> Result<Step> {
+        let inner_env = construct_let_env(args.nth(0)?.as_list()?, env.clone())?;
+        Ok(eval_sequence(args.tl()?, inner_env)?.in_scope(env))
+    }),
+    ("{{}}", |args, env| -> Result<Step> {
+        let mut definitions = Vec::new();
+        for binding in args.nth(0)?.as_list()?.iter() {
+            let binding = binding.as_list()?;
+            definitions.push((binding.nth(0)?, binding.nth(1)?));
+        }
+        let group = eval_group(definitions, &env)?;
+        Ok(eval_sequence(args.tl()?, bind_group(&group, env.clone()))?.in_scope(env))
+    }),
 ];
 
+// The builtins taking forms that are not evaluated as values, such as names and parameter lists.
+const SPECIAL_FORMS: [&str; 9] = [
+    "'",
+    "`",
+    ":=",
+    "->",
+    "macro",
+    "operative",
+    "::=",
+    "{}",
+    "{{}}",
+];
+
+fn quote_values(values: Vec<Exp>) -> List {
+    values
+        .into_iter()
+        .map(|value| {
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                Rc::new(List::Cons(Rc::new(value), Rc::new(List::Nil))),
+            ))
+        })
+        .collect()
+}
+
+pub fn apply_operator(name: &str, args: &List, env: List) -> Result<(Exp, List)> {
+    operator_step(name, args, env)?.run()
+}
+
+// Applies an operator, leaving a form in tail position unevaluated.
+pub fn operator_step(name: &str, args: &List, env: List) -> Result<Step> {
+    for (op, func) in TAIL_OPERATORS.iter() {
+        if *op == name {
+            return func(args, env);
+        }
+    }
+    for (op, func) in PREDEFINED_OPERATORS.iter() {
+        if *op == name {
+            let (res, env) = func(args, env)?;
+            return Ok(Step::Done(res, env));
+        }
+    }
+    Err(LispicoError::UnknownOperator(name.to_string()))
+}
+
+pub fn eval_args(args: &List, env: List) -> Result<(Vec<Exp>, List)> {
+    let mut env = env;
+    let mut values = Vec::new();
+    for arg in args.iter() {
+        let (value, new_env) = arg.eval(env)?;
+        values.push(value);
+        env = new_env;
+    }
+    Ok((values, env))
+}
+
+// Evaluates forms in order, each in the environment left by the one before, the last one in tail
+// position.
+pub fn eval_sequence(forms: &List, env: List) -> Result<Step> {
+    let mut env = env;
+    let mut rest = forms;
+    while let List::Cons(form, tl) = rest {
+        if tl.is_empty() {
+            return Ok(Step::Tail {
+                exp: Exp::clone(form),
+                env,
+                scope: None,
+            });
+        }
+        (_, env) = form.eval(env)?;
+        rest = tl;
+    }
+    Ok(Step::Done(Exp::List(List::Nil), env))
+}
+
+pub fn bind_operator(name: &str, env: List) -> List {
+    let binding = List::Cons(
+        Rc::new(Exp::Atom(Atom::Identifier(name.to_string()))),
+        Rc::new(List::Cons(
+            Rc::new(Exp::Atom(Atom::Identifier(name.to_string()))),
+            Rc::new(List::Nil),
+        )),
+    );
+    List::Cons(Rc::new(Exp::List(binding)), Rc::new(env))
+}
+
 pub fn get_default_env() -> List {
     let mut env = List::Nil;
     for (name, _) in PREDEFINED_OPERATORS.iter() {
-        let binding = List::Cons(
-            Box::new(Exp::Atom(Atom::Identifier(name.to_string()))),
-            Box::new(List::Cons(
-                Box::new(Exp::Atom(Atom::Identifier(name.to_string()))),
-                Box::new(List::Nil),
-            )),
-        );
-        env = List::Cons(Box::new(Exp::List(binding)), Box::new(env));
+        env = bind_operator(name, env);
+    }
+    for (name, _) in TAIL_OPERATORS.iter() {
+        env = bind_operator(name, env);
     }
     env
 }
 
+// Evaluates arguments until one is `decisive` in truth, the last one in tail position.
+fn eval_logical_operator(decisive: bool, args: &List, env: List) -> Result<Step> {
+    let mut env = env;
+    let mut rest = args;
+    while let List::Cons(arg, tl) = rest {
+        if tl.is_empty() {
+            return Ok(Step::Tail {
+                exp: Exp::clone(arg),
+                env,
+                scope: None,
+            });
+        }
+        let (value, new_env) = arg.eval(env)?;
+        if value.is_true() == decisive {
+            return Ok(Step::Done(value, new_env));
+        }
+        env = new_env;
+        rest = tl;
+    }
+    Ok(Step::Done(Exp::truth(!decisive), env))
+}
+
+fn is_function_form(form: &Exp) -> bool {
+    match form.unlocated() {
+        Exp::List(List::Cons(operator, _)) => matches!(
+            operator.unlocated(),
+            Exp::Atom(Atom::Identifier(id)) if id == "->" || id == "macro" || id == "operative"
+        ),
+        _ => false,
+    }
+}
+
+fn construct_closure(params: &Exp, body: &List, env: &List) -> Result<Closure> {
+    let params = params.as_list()?;
+    let signature = parse_params(params)?;
+    Ok(Closure {
+        params: params.clone(),
+        signature: Rc::new(signature),
+        body: body.clone(),
+        env: env.clone(),
+        group: None,
+    })
+}
+
+// Evaluates the values of recursive definitions, all in the environment enclosing them.
+fn eval_group(definitions: Vec<(&Exp, &Exp)>, env: &List) -> Result<Rc<Vec<(Exp, Exp)>>> {
+    let mut group = Vec::new();
+    for (name, value) in definitions {
+        check_bindable(name)?;
+        let name = name.as_atom()?.as_identifier()?;
+        let (value, _) = value.eval(env.clone())?;
+        group.push((Exp::Atom(Atom::Identifier(name.to_string())), value));
+    }
+    Ok(Rc::new(group))
+}
+
 pub fn construct_let_env(bindings: &List, env: List) -> Result<List> {
     if let List::Nil = bindings {
         return Ok(env);
     }
     let hd = bindings.hd()?.as_list()?;
     let name = hd.nth(0)?;
+    check_bindable(name)?;
     let (value, new_env) = hd.nth(1)?.eval(env)?;
     let new_binding = List::Cons(
-        Box::new(name.clone()),
-        Box::new(List::Cons(Box::new(value), Box::new(List::Nil))),
+        Rc::new(name.clone()),
+        Rc::new(List::Cons(Rc::new(value), Rc::new(List::Nil))),
     );
-    let next_env = List::Cons(Box::new(Exp::List(new_binding)), Box::new(new_env));
+    let next_env = List::Cons(Rc::new(Exp::List(new_binding)), Rc::new(new_env));
 
-    return construct_let_env(bindings.tl()?, next_env);
+    construct_let_env(bindings.tl()?, next_env)
 }
 
+fn check_arity(op: &str, arity: Arity, args: &List) -> Result<()> {
+    if !arity.accepts(args.len()) {
+        return Err(LispicoError::ArityMismatch {
+            operator: op.to_string(),
+            expected: arity,
+            found: args.len(),
+        });
+    }
+    Ok(())
+}
+
+fn eval_numeric_args(op: &str, arity: Arity, args: &List, env: List) -> Result<(Vec<Atom>, List)> {
+    check_arity(op, arity, args)?;
+    let (values, env) = eval_args(args, env)?;
+    let mut numbers = Vec::new();
+    for value in values {
+        let atom = value.as_atom()?;
+        atom.as_number()?;
+        numbers.push(atom.clone());
+    }
+    Ok((numbers, env))
+}
+
+// Exact arguments give exact results, using machine integers until they overflow. Any float
+// argument makes the result a float.
+// `-` and `/` with a single argument negate it and take its reciprocal.
 fn eval_numeric_operator(op: &str, args: &List, env: List) -> Result<(Exp, List)> {
-    let (lhs, env) = args.nth(0)?.eval(env)?;
-    let (rhs, env) = args.nth(1)?.eval(env)?;
+    let arity = match op {
+        "+" | "*" => Arity::AtLeast(0),
+        "-" | "/" => Arity::AtLeast(1),
+        _ => Arity::Exactly(2),
+    };
+    let (numbers, env) = eval_numeric_args(op, arity, args, env)?;
+    if matches!(op, "%" | "%%") {
+        if let Some(num) = numbers
+            .iter()
+            .find(|num| !matches!(num, Atom::Integer(_) | Atom::BigInteger(_)))
+        {
+            return Err(LispicoError::type_mismatch(
+                "an integer",
+                &Exp::Atom(num.clone()),
+            ));
+        }
+    }
 
-    let x = lhs.as_atom()?.as_number()?;
-    let y = rhs.as_atom()?.as_number()?;
+    let integers = numbers
+        .iter()
+        .map(|num| num.as_integer().ok())
+        .collect::<Option<Vec<i64>>>();
+    if let Some(result) = integers.and_then(|integers| integer_operation(op, &integers)) {
+        return Ok((Exp::Atom(Atom::Integer(result?)), env));
+    }
+    let exact = numbers
+        .iter()
+        .map(Atom::as_exact)
+        .collect::<Option<Vec<BigRational>>>();
+    if let Some(result) = exact.and_then(|exact| exact_operation(op, &exact)) {
+        return Ok((Exp::Atom(Atom::exact(result?)), env));
+    }
+    let floats = numbers
+        .iter()
+        .map(Atom::as_number)
+        .collect::<Result<Vec<f64>>>()?;
+    Ok((Exp::Atom(Atom::Number(float_operation(op, &floats)?)), env))
+}
 
-    let result = match op {
-        "+" => x + y,
-        "-" => x - y,
-        "*" => x * y,
-        "/" => {
-            if y == 0.0 {
-                return Err("Division by zero".into());
+// `None` if the result is not an integer, or does not fit in one.
+fn integer_operation(op: &str, numbers: &[i64]) -> Option<Result<i64>> {
+    if matches!(op, "/" | "//" | "%" | "%%") && numbers[1..].contains(&0) {
+        return Some(Err(LispicoError::DivisionByZero));
+    }
+    let result = match (op, numbers) {
+        ("+", _) => numbers
+            .iter()
+            .try_fold(0, |acc: i64, y| acc.checked_add(*y)),
+        ("*", _) => numbers
+            .iter()
+            .try_fold(1, |acc: i64, y| acc.checked_mul(*y)),
+        ("-", [x]) => x.checked_neg(),
+        ("-", [x, rest @ ..]) => rest.iter().try_fold(*x, |acc, y| acc.checked_sub(*y)),
+        ("/", [x]) => exact_quotient(1, &[*x]),
+        ("/", [x, rest @ ..]) => exact_quotient(*x, rest),
+        ("//", [x, y]) => match x.checked_div(*y) {
+            Some(q) if x % y != 0 && (*x < 0) != (*y < 0) => Some(q - 1),
+            q => q,
+        },
+        ("^", [x, y]) => u32::try_from(*y).ok().and_then(|y| x.checked_pow(y)),
+        ("%", [x, y]) => x.checked_rem(*y),
+        ("%%", [x, y]) => match x.checked_rem(*y) {
+            // Unlike the remainder, the modulo takes the sign of the divisor.
+            Some(r) if r != 0 && (r < 0) != (*y < 0) => Some(r + y),
+            r => r,
+        },
+        _ => unreachable!(),
+    };
+    result.map(Ok)
+}
+
+fn exact_quotient(x: i64, divisors: &[i64]) -> Option<i64> {
+    let mut acc = x;
+    for y in divisors {
+        match acc.checked_rem(*y) {
+            Some(0) => acc /= y,
+            _ => return None,
+        }
+    }
+    Some(acc)
+}
+
+// `None` if the result is not exact, i.e. for a power with a fractional exponent.
+fn exact_operation(op: &str, numbers: &[BigRational]) -> Option<Result<BigRational>> {
+    if matches!(op, "/" | "//" | "%" | "%%") && numbers.iter().skip(1).any(Zero::is_zero) {
+        return Some(Err(LispicoError::DivisionByZero));
+    }
+    let result = match (op, numbers) {
+        ("+", _) => numbers.iter().sum(),
+        ("*", _) => numbers.iter().product(),
+        ("-", [x]) => -x,
+        ("-", [x, rest @ ..]) => rest.iter().fold(x.clone(), |acc, y| acc - y),
+        ("/", [x]) if x.is_zero() => return Some(Err(LispicoError::DivisionByZero)),
+        ("/", [x]) => x.recip(),
+        ("/", [x, rest @ ..]) => rest.iter().fold(x.clone(), |acc, y| acc / y),
+        ("//", [x, y]) => (x / y).floor(),
+        ("^", [x, y]) => {
+            // A fractional power is generally irrational, and so computed with floats.
+            if !y.is_integer() {
+                return None;
+            }
+            let exponent = y.to_integer();
+            if x.is_zero() && exponent.is_negative() {
+                return Some(Err(LispicoError::DivisionByZero));
             }
-            x / y
+            match exponent.to_i32() {
+                Some(exponent) => x.pow(exponent),
+                // Only 0, 1 and -1 have powers this large that are small enough to compute.
+                None if x.is_zero() || x.abs().is_one() => {
+                    let odd = !(&exponent % 2u32).is_zero();
+                    if x.is_negative() && !odd {
+                        -x
+                    } else {
+                        x.clone()
+                    }
+                }
+                None => {
+                    return Some(Err(LispicoError::Custom(format!(
+                        "Exponent {exponent} is too large for an exact power"
+                    ))))
+                }
+            }
+        }
+        ("%", [x, y]) => BigRational::from_integer(x.to_integer() % y.to_integer()),
+        ("%%", [x, y]) => {
+            let (x, y) = (x.to_integer(), y.to_integer());
+            let r = &x % &y;
+            let r = if !r.is_zero() && r.is_negative() != y.is_negative() {
+                r + y
+            } else {
+                r
+            };
+            BigRational::from_integer(r)
         }
-        "^" => x.powf(y),
         _ => unreachable!(),
     };
+    Some(Ok(result))
+}
+
+fn float_operation(op: &str, numbers: &[f64]) -> Result<f64> {
+    let result = match (op, numbers) {
+        ("+", _) => numbers.iter().sum(),
+        ("*", _) => numbers.iter().product(),
+        ("-", [x]) => -x,
+        ("-", [x, rest @ ..]) => rest.iter().fold(*x, |acc, y| acc - y),
+        ("/", [x]) => divide(1.0, *x)?,
+        ("/", [x, rest @ ..]) => rest.iter().try_fold(*x, |acc, y| divide(acc, *y))?,
+        ("//", [x, y]) => divide(*x, *y)?.floor(),
+        ("^", [x, y]) => x.powf(*y),
+        _ => unreachable!(),
+    };
+    Ok(result)
+}
+
+fn divide(x: f64, y: f64) -> Result<f64> {
+    if y == 0.0 {
+        return Err(LispicoError::DivisionByZero);
+    }
+    Ok(x / y)
+}
+
+// Whether each argument compares to the next, except for `!=`, which holds if no two are equal.
+// `=` also accepts values other than numbers, which are equal when they are structurally so, with
+// the numbers in lists compared by value as well.
+fn eval_comparison_operator(op: &str, args: &List, env: List) -> Result<(Exp, List)> {
+    if op == "=" {
+        check_arity(op, Arity::AtLeast(1), args)?;
+        let (values, env) = eval_args(args, env)?;
+        let mut equal = true;
+        for pair in values.windows(2) {
+            equal = equal && equal_values(&pair[0], &pair[1])?;
+        }
+        return Ok((Exp::truth(equal), env));
+    }
+    let (numbers, env) = eval_numeric_args(op, Arity::AtLeast(1), args, env)?;
+    Ok((Exp::truth(compare(op, &numbers)?), env))
+}
+
+fn equal_values(lhs: &Exp, rhs: &Exp) -> Result<bool> {
+    match (lhs, rhs) {
+        (Exp::Atom(x), Exp::Atom(y)) if x.as_number().is_ok() && y.as_number().is_ok() => {
+            compare("=", &[x.clone(), y.clone()])
+        }
+        (Exp::List(xs), Exp::List(ys)) => {
+            if xs.len() != ys.len() {
+                return Ok(false);
+            }
+            for (x, y) in xs.iter().zip(ys.iter()) {
+                if !equal_values(x, y)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        _ => Ok(lhs == rhs),
+    }
+}
+
+// Numbers compare by value, whatever their representation.
+fn compare(op: &str, numbers: &[Atom]) -> Result<bool> {
+    let integers = numbers
+        .iter()
+        .map(|num| num.as_integer().ok())
+        .collect::<Option<Vec<i64>>>();
+    if let Some(integers) = integers {
+        return Ok(compare_values(op, &integers));
+    }
+    let exact = numbers
+        .iter()
+        .map(Atom::as_exact)
+        .collect::<Option<Vec<BigRational>>>();
+    if let Some(exact) = exact {
+        return Ok(compare_values(op, &exact));
+    }
+    let floats = numbers
+        .iter()
+        .map(Atom::as_number)
+        .collect::<Result<Vec<f64>>>()?;
+    Ok(compare_values(op, &floats))
+}
+
+fn compare_values<T: PartialOrd>(op: &str, numbers: &[T]) -> bool {
+    match op {
+        "!=" => numbers
+            .iter()
+            .enumerate()
+            .all(|(i, x)| numbers[i + 1..].iter().all(|y| x != y)),
+        _ => numbers.windows(2).all(|pair| match op {
+            "=" => pair[0] == pair[1],
+            "<" => pair[0] < pair[1],
+            ">" => pair[0] > pair[1],
+            "<=" => pair[0] <= pair[1],
+            ">=" => pair[0] >= pair[1],
+            _ => unreachable!(),
+        }),
+    }
+}
 
-    Ok((Exp::Atom(Atom::Number(result)), env))
+// Strings are measured and indexed in characters rather than bytes.
+fn eval_string_operator(op: &str, args: &List, env: List) -> Result<(Exp, List)> {
+    let arity = match op {
+        "string-concat" => Arity::AtLeast(0),
+        "substring" => Arity::Between(2, 3),
+        "string-replace" => Arity::Exactly(3),
+        "string-index" | "string-split" | "string-join" | "string-starts-with"
+        | "string-ends-with" => Arity::Exactly(2),
+        _ => Arity::Exactly(1),
+    };
+    check_arity(op, arity, args)?;
+    let (values, env) = eval_args(args, env)?;
+    let string = |i: usize| values[i].as_atom()?.as_string();
+    let res = match op {
+        "string-concat" => {
+            let mut res = String::new();
+            for value in &values {
+                res.push_str(value.as_atom()?.as_string()?);
+            }
+            Exp::Atom(Atom::String(res))
+        }
+        "string-length" => Exp::Atom(Atom::Integer(string(0)?.chars().count() as i64)),
+        "substring" => {
+            let text = string(0)?;
+            let len = text.chars().count();
+            let start = char_index(&values[1], len)?;
+            let end = match values.get(2) {
+                Some(end) => char_index(end, len)?,
+                None => len,
+            };
+            if start > end {
+                return Err(LispicoError::Custom(format!(
+                    "Substring start {start} is after its end {end}"
+                )));
+            }
+            Exp::Atom(Atom::String(
+                text.chars().skip(start).take(end - start).collect(),
+            ))
+        }
+        "string-index" => {
+            let text = string(0)?;
+            match text.find(string(1)?) {
+                Some(offset) => Exp::Atom(Atom::Integer(text[..offset].chars().count() as i64)),
+                None => Exp::List(List::Nil),
+            }
+        }
+        // An empty separator splits a string into its characters.
+        "string-split" => {
+            let (text, separator) = (string(0)?, string(1)?);
+            let parts: Vec<String> = if separator.is_empty() {
+                text.chars().map(String::from).collect()
+            } else {
+                text.split(separator).map(String::from).collect()
+            };
+            Exp::List(
+                parts
+                    .into_iter()
+                    .map(|part| Exp::Atom(Atom::String(part)))
+                    .collect(),
+            )
+        }
+        "string-join" => {
+            let mut parts = Vec::new();
+            for part in values[0].as_list()?.iter() {
+                parts.push(part.as_atom()?.as_string()?);
+            }
+            Exp::Atom(Atom::String(parts.join(string(1)?)))
+        }
+        "string-trim" => Exp::Atom(Atom::String(string(0)?.trim().to_string())),
+        "string-upper" => Exp::Atom(Atom::String(string(0)?.to_uppercase())),
+        "string-lower" => Exp::Atom(Atom::String(string(0)?.to_lowercase())),
+        "string-replace" => Exp::Atom(Atom::String(string(0)?.replace(string(1)?, string(2)?))),
+        "string-starts-with" => Exp::truth(string(0)?.starts_with(string(1)?)),
+        "string-ends-with" => Exp::truth(string(0)?.ends_with(string(1)?)),
+        // Text that is not a number gives `()`.
+        "string->number" => match parse_number(string(0)?.trim()) {
+            Some(num) => Exp::Atom(num),
+            None => Exp::List(List::Nil),
+        },
+        "number->string" => {
+            let atom = values[0].as_atom()?;
+            atom.as_number()?;
+            Exp::Atom(Atom::String(atom.to_string()))
+        }
+        "symbol->string" => Exp::Atom(Atom::String(
+            values[0].as_atom()?.as_identifier()?.to_string(),
+        )),
+        "string->symbol" => Exp::Atom(Atom::Identifier(string(0)?.to_string())),
+        _ => unreachable!(),
+    };
+    Ok((res, env))
+}
+
+// A character position in a string of the given length, which may be at its end.
+fn char_index(index: &Exp, len: usize) -> Result<usize> {
+    let index = index.as_atom()?.as_integer()?;
+    match usize::try_from(index) {
+        Ok(index) if index <= len => Ok(index),
+        _ => Err(LispicoError::Custom(format!(
+            "Index {index} is out of range for a string of length {len}"
+        ))),
+    }
 }
@@ -1,525 +1,1949 @@
-#[cfg(test)]
-mod tests {
-    use pest::Parser;
+use std::rc::Rc;
 
-    use crate::*;
-    use exp::*;
-    use parser::*;
+use pest::Parser;
 
-    #[test]
-    fn parser() {
-        let programs = vec![
+use crate::*;
+use exp::*;
+use parser::*;
+
+#[test]
+fn parser() {
+    let programs = vec![
+        "(a)",
+        "(a b)",
+        "(a b c)",
+        "()",
+        "(())",
+        "((a))",
+        "(a (b))",
+        "(a (b c))",
+        "('a)",
+        "('a 'b)",
+        "(a 'b c)",
+        "( a )",
+        "(? 't 'a 'b)",
+        "(? 't 'a)",
+        "\"abc\"",
+        "(:= x \"abc\")",
+        "(a) (b)",
+        "a\n(b c)\n\"d\"",
+        "",
+        "; comment",
+        "(a ; comment (\n b)",
+        "(a ;| block ( comment |; b)",
+        "(a ;| outer ;| inner |; still outer |; b)",
+        ";| multi\nline |; (a)",
+        "(a ;_(b c) d)",
+        "(a ;_'b c)",
+        "(a ;_\" b ;\" c)",
+        "(a ;_b ;_c d)",
+        "(a ;_ note (b)\n c)",
+        "(a ;_\n b)",
+        "\"a \\\"quoted\\\" word\"",
+        "\"\\n\\t\\r\\0\\\\\\u{1F600}\"",
+        "\"\"\"raw \"text\" \\n block\"\"\"",
+        "(a \"\" \"\"\"\"\"\")",
+        "(# \"file\")",
+        "(a `(b ,c ,@d) `e)",
+        "(a `(b '(c ,d) `(e ,f)))",
+        "(a ;_`(b ,c) d)",
+        "'a",
+        "`(a ,b) 'c",
+    ];
+    for program in programs {
+        assert!(LispicoParser::parse(Rule::program, program).is_ok());
+    }
+
+    let faulty_programs = vec![
+        "(",
+        ")",
+        "(a",
+        "a)",
+        "(a b",
+        "(a b c",
+        "(' a)",
+        "\"adfg",
+        "(a ;| unterminated )",
+        "(a ;| outer ;| inner |; b)",
+        "(a ; comment )",
+        "(a ;_)",
+        "(a ;_(b)",
+        ";_ note\n;| unterminated",
+        ";|---- header ----",
+        "\"\\\"",
+        "\"\\q\"",
+        "\"\\u{}\"",
+        "\"\"\"unterminated\"\"",
+        "(a, b)",
+        "(a ,b)",
+        "(a ,@b)",
+        "(a `(b ,))",
+    ];
+    for program in faulty_programs {
+        assert!(LispicoParser::parse(Rule::program, program).is_err());
+    }
+}
+
+#[test]
+fn exp() {
+    let programs = vec![
+        (
             "(a)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
             "(a b)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                    Rc::new(List::Nil),
+                )),
+            )),
+        ),
+        (
             "(a b c)",
-            "()",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("c".to_string()))),
+                        Rc::new(List::Nil),
+                    )),
+                )),
+            )),
+        ),
+        ("()", Exp::List(List::Nil)),
+        (
             "(())",
+            Exp::List(List::Cons(
+                Rc::new(Exp::List(List::Nil)),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
             "((a))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::List(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                    Rc::new(List::Nil),
+                ))),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
             "(a (b))",
-            "(a (b c))",
-            "('a)",
-            "('a 'b)",
-            "(a 'b c)",
-            "( a )",
-            "(? 't 'a 'b)",
-            "(? 't 'a)",
-            "\"abc\"",
-            "(:= x \"abc\")",
-        ];
-        for program in programs {
-            assert!(LispicoParser::parse(Rule::program, program).is_ok());
-        }
-
-        let faulty_programs = vec!["(", ")", "(a", "a)", "(a b", "(a b c", "(' a)", "\"adfg"];
-        for program in faulty_programs {
-            assert!(LispicoParser::parse(Rule::program, program).is_err());
-        }
-    }
-
-    #[test]
-    fn exp() {
-        let programs = vec![
-            (
-                "(a)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Nil),
-                )),
-            ),
-            (
-                "(a b)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                        Box::new(List::Nil),
-                    )),
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::List(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                        Rc::new(List::Nil),
+                    ))),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            (
-                "(a b c)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("c".to_string()))),
-                            Box::new(List::Nil),
+            )),
+        ),
+        (
+            "(a (b c))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::List(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                        Rc::new(List::Cons(
+                            Rc::new(Exp::Atom(Atom::Identifier("c".to_string()))),
+                            Rc::new(List::Nil),
                         )),
-                    )),
-                )),
-            ),
-            ("()", Exp::List(List::Nil)),
-            (
-                "(())",
-                Exp::List(List::Cons(
-                    Box::new(Exp::List(List::Nil)),
-                    Box::new(List::Nil),
-                )),
-            ),
-            (
-                "((a))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::List(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                        Box::new(List::Nil),
                     ))),
-                    Box::new(List::Nil),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            (
-                "(a (b))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::List(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                            Box::new(List::Nil),
-                        ))),
-                        Box::new(List::Nil),
+            )),
+        ),
+        (
+            "('a)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::List(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                        Rc::new(List::Nil),
                     )),
-                )),
-            ),
-            (
-                "(a (b c))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::List(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                            Box::new(List::Cons(
-                                Box::new(Exp::Atom(Atom::Identifier("c".to_string()))),
-                                Box::new(List::Nil),
-                            )),
-                        ))),
-                        Box::new(List::Nil),
+                ))),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
+            "('a 'b)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::List(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                        Rc::new(List::Nil),
                     )),
-                )),
-            ),
-            (
-                "('a)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::List(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                            Box::new(List::Nil),
+                ))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::List(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                        Rc::new(List::Cons(
+                            Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                            Rc::new(List::Nil),
                         )),
                     ))),
-                    Box::new(List::Nil),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            (
-                "('a 'b)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::List(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                            Box::new(List::Nil),
+            )),
+        ),
+        (
+            "(a 'b c)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::List(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                        Rc::new(List::Cons(
+                            Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                            Rc::new(List::Nil),
                         )),
                     ))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::List(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-                            Box::new(List::Cons(
-                                Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                                Box::new(List::Nil),
-                            )),
-                        ))),
-                        Box::new(List::Nil),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("c".to_string()))),
+                        Rc::new(List::Nil),
                     )),
                 )),
-            ),
-            (
-                "(a 'b c)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::List(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-                            Box::new(List::Cons(
-                                Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                                Box::new(List::Nil),
-                            )),
-                        ))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("c".to_string()))),
-                            Box::new(List::Nil),
-                        )),
-                    )),
-                )),
-            ),
-            (
-                "( a )",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Nil),
-                )),
-            ),
-        ];
-
-        for (program, expected) in programs {
-            let pairs = LispicoParser::parse(Rule::program, program)
-                .unwrap()
-                .next()
-                .unwrap();
-            let exp = construct_exp(pairs);
-            assert_eq!(exp, expected, "program: {}", program);
-        }
+            )),
+        ),
+        (
+            "( a )",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Nil),
+            )),
+        ),
+    ];
+
+    for (program, expected) in programs {
+        let pairs = LispicoParser::parse(Rule::program, program)
+            .unwrap()
+            .next()
+            .unwrap();
+        let exp = construct_exp(pairs);
+        assert_eq!(exp, expected, "program: {}", program);
     }
+}
 
-    #[test]
-    fn parse_identifier() {
-        let identifiers = vec![
-            "a", "aa", ".", ".<", ".>", "$", "@", "<<", ">>", "=", "_a", "a_", "a_a-a_",
-        ];
-
-        for identifier in identifiers {
-            let pairs = LispicoParser::parse(Rule::identifier, identifier);
-            assert!(
-                pairs.is_ok() && pairs.unwrap().len() == 1,
-                "identifier: {identifier}"
-            );
-        }
+#[test]
+fn parse_identifier() {
+    let identifiers = vec![
+        "a",
+        "aa",
+        ".",
+        ".<",
+        ".>",
+        "$",
+        "@",
+        "<<",
+        ">>",
+        "=",
+        "_a",
+        "a_",
+        "a_a-a_",
+        "x1",
+        "list2",
+        "a1b2",
+        "empty?",
+        "set!",
+        "->str",
+        "->",
+        "string->number",
+        "<=",
+        "-",
+        "-x",
+        "-5a",
+        "+1+",
+        "größe",
+        "λ",
+        "café",
+        "日本語",
+        "x_1-y?",
+        "#",
+        ":=",
+        "{}",
+    ];
 
-        let invalid_identifiers = vec!["", "(", "[", "\"", " ", "'"];
-        for identifier in invalid_identifiers {
-            let pairs = LispicoParser::parse(Rule::identifier, identifier);
-            assert!(pairs.is_err(), "identifier: {identifier}");
-        }
+    for identifier in identifiers {
+        let pairs = LispicoParser::parse(Rule::identifier, identifier);
+        assert!(
+            pairs.is_ok() && pairs.unwrap().as_str() == identifier,
+            "identifier: {identifier}"
+        );
     }
 
-    #[test]
-    fn eval() {
-        let programs = vec![
-            ("()", Exp::List(List::Nil)),
-            (
-                "(. 'a '(b))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                        Box::new(List::Nil),
-                    )),
-                )),
-            ),
-            ("(.< '(a b))", Exp::Atom(Atom::Identifier("a".to_string()))),
-            (
-                "(.> '(a b))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                    Box::new(List::Nil),
-                )),
-            ),
-            (
-                "(. 'a ())",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Nil),
+    let invalid_identifiers = vec![
+        "", "(", "[", "\"", " ", "'", "1", "-5", "+5", ".5", "-.5", "+1/2", "1.5", "-1.5", "1a",
+        "9lives", ";a",
+    ];
+    for identifier in invalid_identifiers {
+        let pairs = LispicoParser::parse(Rule::identifier, identifier);
+        assert!(pairs.is_err(), "identifier: {identifier}");
+    }
+}
+
+#[test]
+fn eval() {
+    let programs = vec![
+        ("()", Exp::List(List::Nil)),
+        (
+            "(. 'a '(b))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            ("(= 1 1)", Exp::Atom(Atom::Identifier("t".to_string()))),
-            ("(= 1 2)", Exp::List(List::Nil)),
-            ("(= 'a 'a)", Exp::Atom(Atom::Identifier("t".to_string()))),
-            ("(= 'a 'b)", Exp::List(List::Nil)),
-            (
-                "(= '(1 2 3) '(1 2 3))",
-                Exp::Atom(Atom::Identifier("t".to_string())),
-            ),
-            ("(= '(1 2 3) '(1 2))", Exp::List(List::Nil)),
-            ("(? 't 'a 'b)", Exp::Atom(Atom::Identifier("a".to_string()))),
-            ("(? () 'a 'b)", Exp::Atom(Atom::Identifier("b".to_string()))),
-            ("(? 't 'a)", Exp::Atom(Atom::Identifier("a".to_string()))),
-            ("(? () 'a)", Exp::List(List::Nil)),
-            (
-                "({} ((x 'a) (y 'b)) (. x (. y ())))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                        Box::new(List::Nil),
-                    )),
+            )),
+        ),
+        ("(.< '(a b))", Exp::Atom(Atom::Identifier("a".to_string()))),
+        (
+            "(.> '(a b))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
+            "(. 'a ())",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Nil),
+            )),
+        ),
+        ("(= 1 1)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(= 1 2)", Exp::List(List::Nil)),
+        ("(= 'a 'a)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(= 'a 'b)", Exp::List(List::Nil)),
+        (
+            "(= '(1 2 3) '(1 2 3))",
+            Exp::Atom(Atom::Identifier("t".to_string())),
+        ),
+        ("(= '(1 2 3) '(1 2))", Exp::List(List::Nil)),
+        ("(? 't 'a 'b)", Exp::Atom(Atom::Identifier("a".to_string()))),
+        ("(? () 'a 'b)", Exp::Atom(Atom::Identifier("b".to_string()))),
+        ("(? 't 'a)", Exp::Atom(Atom::Identifier("a".to_string()))),
+        ("(? () 'a)", Exp::List(List::Nil)),
+        (
+            "({} ((x 'a) (y 'b)) (. x (. y ())))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            (
-                "({} ((x 'a) (y x)) (. x (. y ())))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                        Box::new(List::Nil),
-                    )),
+            )),
+        ),
+        (
+            "({} ((x 'a) (y x)) (. x (. y ())))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            ("(+ 1 2)", Exp::Atom(Atom::Number(3.0))),
-            ("(+ -1 2)", Exp::Atom(Atom::Number(1.0))),
-            ("(+ 1 (+ 2 3))", Exp::Atom(Atom::Number(6.0))),
-            ("(- 2 1)", Exp::Atom(Atom::Number(1.0))),
-            ("(* 2 3)", Exp::Atom(Atom::Number(6.0))),
-            ("(/ 6 2)", Exp::Atom(Atom::Number(3.0))),
-            ("(^ 2 3)", Exp::Atom(Atom::Number(8.0))),
+            )),
+        ),
+        ("(+ 1 2)", Exp::Atom(Atom::Integer(3))),
+        ("(+ -1 2)", Exp::Atom(Atom::Integer(1))),
+        ("(+ 1 (+ 2 3))", Exp::Atom(Atom::Integer(6))),
+        ("(- 2 1)", Exp::Atom(Atom::Integer(1))),
+        ("(* 2 3)", Exp::Atom(Atom::Integer(6))),
+        ("(/ 6 2)", Exp::Atom(Atom::Integer(3))),
+        ("(^ 2 3)", Exp::Atom(Atom::Integer(8))),
+        ("(+)", Exp::Atom(Atom::Integer(0))),
+        ("(+ 1 2 3 4)", Exp::Atom(Atom::Integer(10))),
+        ("(*)", Exp::Atom(Atom::Integer(1))),
+        ("(* 2 3 4)", Exp::Atom(Atom::Integer(24))),
+        ("(- 5)", Exp::Atom(Atom::Integer(-5))),
+        ("(- 10 1 2 3)", Exp::Atom(Atom::Integer(4))),
+        ("(/ 4.0)", Exp::Atom(Atom::Number(0.25))),
+        ("(/ 60 2 3)", Exp::Atom(Atom::Integer(10))),
+        ("(% 7 3)", Exp::Atom(Atom::Integer(1))),
+        ("(% -7 3)", Exp::Atom(Atom::Integer(-1))),
+        ("(%% -7 3)", Exp::Atom(Atom::Integer(2))),
+        ("(%% 7 -3)", Exp::Atom(Atom::Integer(-2))),
+        ("(%% 6 3)", Exp::Atom(Atom::Integer(0))),
+        ("(< 1 2 3)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(< 1 3 2)", Exp::List(List::Nil)),
+        ("(> 3 2 1)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(<= 1 1 2)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(>= 2 2 3)", Exp::List(List::Nil)),
+        ("(< 1)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(!= 1 2 3)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(!= 1 2 1)", Exp::List(List::Nil)),
+        ("(= 1 1 1)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(= 1 1 2)", Exp::List(List::Nil)),
+        ("(= 2 1 1)", Exp::List(List::Nil)),
+        ("(= 'a 'a 'a)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        ("(= 1 1.0 'a)", Exp::List(List::Nil)),
+        ("(= 1)", Exp::Atom(Atom::Identifier("t".to_string()))),
+        (
+            "
             (
-                "
                 (
-                    (
-                        ->
-                        (x y)
-                        (. x (. y ()))
-                    )
-                    'a
-                    'b
+                    ->
+                    (x y)
+                    (. x (. y ()))
                 )
-                ",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                        Box::new(List::Nil),
-                    )),
+                'a
+                'b
+            )
+            ",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                    Rc::new(List::Nil),
                 )),
-            ),
-        ];
-
-        for (program, expected) in programs {
-            let pairs = LispicoParser::parse(Rule::program, program)
-                .unwrap()
-                .next()
-                .unwrap();
-            let exp = construct_exp(pairs);
-            let (res, _) = exp.eval(get_default_env()).unwrap();
-            assert_eq!(res, expected, "program: {program}");
-        }
+            )),
+        ),
+    ];
+
+    for (program, expected) in programs {
+        let pairs = LispicoParser::parse(Rule::program, program)
+            .unwrap()
+            .next()
+            .unwrap();
+        let exp = construct_exp(pairs);
+        let (res, _) = exp.eval(get_default_env()).unwrap();
+        assert_eq!(res, expected, "program: {program}");
     }
+}
 
-    #[test]
-    fn display() {
-        let programs = vec![
-            (
-                "(a)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Nil),
-                )),
-            ),
-            (
-                "(a b)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                        Box::new(List::Nil),
-                    )),
+#[test]
+fn display() {
+    let programs = vec![
+        (
+            "(a)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
+            "(a b)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            (
-                "(a b c)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("c".to_string()))),
-                            Box::new(List::Nil),
-                        )),
+            )),
+        ),
+        (
+            "(a b c)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("c".to_string()))),
+                        Rc::new(List::Nil),
                     )),
                 )),
-            ),
-            ("()", Exp::List(List::Nil)),
-            (
-                "(())",
-                Exp::List(List::Cons(
-                    Box::new(Exp::List(List::Nil)),
-                    Box::new(List::Nil),
+            )),
+        ),
+        ("()", Exp::List(List::Nil)),
+        (
+            "(())",
+            Exp::List(List::Cons(
+                Rc::new(Exp::List(List::Nil)),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
+            "((a))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::List(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                    Rc::new(List::Nil),
+                ))),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
+            "(a (b))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::List(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                        Rc::new(List::Nil),
+                    ))),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            (
-                "((a))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::List(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                        Box::new(List::Nil),
+            )),
+        ),
+        (
+            "(a (b c))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::List(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                        Rc::new(List::Cons(
+                            Rc::new(Exp::Atom(Atom::Identifier("c".to_string()))),
+                            Rc::new(List::Nil),
+                        )),
                     ))),
-                    Box::new(List::Nil),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            (
-                "(a (b))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::List(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                            Box::new(List::Nil),
-                        ))),
-                        Box::new(List::Nil),
+            )),
+        ),
+        (
+            "('a)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::List(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                        Rc::new(List::Nil),
                     )),
-                )),
-            ),
-            (
-                "(a (b c))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::List(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                            Box::new(List::Cons(
-                                Box::new(Exp::Atom(Atom::Identifier("c".to_string()))),
-                                Box::new(List::Nil),
-                            )),
-                        ))),
-                        Box::new(List::Nil),
+                ))),
+                Rc::new(List::Nil),
+            )),
+        ),
+        (
+            "('a 'b)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::List(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                        Rc::new(List::Nil),
                     )),
-                )),
-            ),
-            (
-                "('a)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::List(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                            Box::new(List::Nil),
+                ))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::List(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                        Rc::new(List::Cons(
+                            Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                            Rc::new(List::Nil),
                         )),
                     ))),
-                    Box::new(List::Nil),
+                    Rc::new(List::Nil),
                 )),
-            ),
-            (
-                "('a 'b)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::List(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                            Box::new(List::Nil),
+            )),
+        ),
+        (
+            "(a 'b c)",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("a".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::List(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("'".to_string()))),
+                        Rc::new(List::Cons(
+                            Rc::new(Exp::Atom(Atom::Identifier("b".to_string()))),
+                            Rc::new(List::Nil),
                         )),
                     ))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::List(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-                            Box::new(List::Cons(
-                                Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                                Box::new(List::Nil),
-                            )),
-                        ))),
-                        Box::new(List::Nil),
-                    )),
-                )),
-            ),
-            (
-                "(a 'b c)",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("a".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::List(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("'".to_string()))),
-                            Box::new(List::Cons(
-                                Box::new(Exp::Atom(Atom::Identifier("b".to_string()))),
-                                Box::new(List::Nil),
-                            )),
-                        ))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("c".to_string()))),
-                            Box::new(List::Nil),
-                        )),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("c".to_string()))),
+                        Rc::new(List::Nil),
                     )),
                 )),
-            ),
-        ];
+            )),
+        ),
+    ];
 
-        for (program, exp) in programs {
-            assert_eq!(program, exp.to_string(), "program: {program}");
-        }
+    for (program, exp) in programs {
+        assert_eq!(program, exp.to_string(), "program: {program}");
     }
+}
 
-    #[test]
-    fn env() {
-        let cases = vec![
-            (
-                "(? a 'b 'c)",
-                "((a x))",
-                Exp::Atom(Atom::Identifier("b".to_string())),
-            ),
-            (
-                "(? a 'b 'c)",
-                "((b x))",
-                Exp::Atom(Atom::Identifier("c".to_string())),
-            ),
-            (
-                "(. a b)",
-                "((a x) (b (y z)))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("x".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("y".to_string()))),
-                        Box::new(List::Cons(
-                            Box::new(Exp::Atom(Atom::Identifier("z".to_string()))),
-                            Box::new(List::Nil),
-                        )),
+#[test]
+fn env() {
+    let cases = vec![
+        (
+            "(? a 'b 'c)",
+            "((a x))",
+            Exp::Atom(Atom::Identifier("b".to_string())),
+        ),
+        (
+            "(? a 'b 'c)",
+            "((b x))",
+            Exp::Atom(Atom::Identifier("c".to_string())),
+        ),
+        (
+            "(. a b)",
+            "((a x) (b (y z)))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("x".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("y".to_string()))),
+                    Rc::new(List::Cons(
+                        Rc::new(Exp::Atom(Atom::Identifier("z".to_string()))),
+                        Rc::new(List::Nil),
                     )),
                 )),
-            ),
-            (
-                "(.< a)",
-                "((a (x y z)))",
-                Exp::Atom(Atom::Identifier("x".to_string())),
-            ),
-            (
-                "(.> a)",
-                "((a (x y z)))",
-                Exp::List(List::Cons(
-                    Box::new(Exp::Atom(Atom::Identifier("y".to_string()))),
-                    Box::new(List::Cons(
-                        Box::new(Exp::Atom(Atom::Identifier("z".to_string()))),
-                        Box::new(List::Nil),
-                    )),
+            )),
+        ),
+        (
+            "(.< a)",
+            "((a (x y z)))",
+            Exp::Atom(Atom::Identifier("x".to_string())),
+        ),
+        (
+            "(.> a)",
+            "((a (x y z)))",
+            Exp::List(List::Cons(
+                Rc::new(Exp::Atom(Atom::Identifier("y".to_string()))),
+                Rc::new(List::Cons(
+                    Rc::new(Exp::Atom(Atom::Identifier("z".to_string()))),
+                    Rc::new(List::Nil),
                 )),
-            ),
-        ];
-
-        for (program_str, env_str, expected) in cases {
-            let program_pair = LispicoParser::parse(Rule::program, program_str)
-                .unwrap()
-                .next()
-                .unwrap();
-            let env_pair = LispicoParser::parse(Rule::program, env_str)
-                .unwrap()
-                .next()
-                .unwrap();
-            let program = construct_exp(program_pair);
-            let env_exp = construct_exp(env_pair);
-            let env;
-            if let Exp::List(list) = env_exp {
-                env = list.extend(&get_default_env());
-            } else {
-                panic!("Expected a list, but got an atom");
-            }
-            let (res, _) = program.eval(env).unwrap();
-            assert_eq!(
-                res, expected,
-                "program: {program_str}, env: {env_str}, res: {res}"
-            );
+            )),
+        ),
+    ];
+
+    for (program_str, env_str, expected) in cases {
+        let program_pair = LispicoParser::parse(Rule::program, program_str)
+            .unwrap()
+            .next()
+            .unwrap();
+        let env_pair = LispicoParser::parse(Rule::program, env_str)
+            .unwrap()
+            .next()
+            .unwrap();
+        let program = construct_exp(program_pair);
+        let env_exp = construct_exp(env_pair);
+        let env;
+        if let Exp::List(list) = env_exp.unlocated() {
+            env = list.extend(&get_default_env());
+        } else {
+            panic!("Expected a list, but got an atom");
         }
+        let (res, _) = program.eval(env).unwrap();
+        assert_eq!(
+            res, expected,
+            "program: {program_str}, env: {env_str}, res: {res}"
+        );
+    }
+}
+
+#[test]
+fn interpreter() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.eval_str("(:= x 2)")?, Exp::List(List::Nil));
+    assert_eq!(
+        interpreter.eval_str("(+ x 3)")?,
+        Exp::Atom(Atom::Integer(5))
+    );
+    assert!(interpreter.eval_str("(+ x").is_err());
+
+    let exp = parser::parse("(* x x)")?;
+    assert_eq!(interpreter.eval_exp(&exp)?, Exp::Atom(Atom::Integer(4)));
+    assert!(interpreter.bindings().len() > get_default_env().len());
+    Ok(())
+}
+
+#[test]
+fn host_operators() -> Result<()> {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let calls = Rc::new(Cell::new(0));
+    let mut interpreter = Interpreter::new();
+    let counter = calls.clone();
+    interpreter.register_operator("count", move |args, env| {
+        let (values, env) = predefined::eval_args(args, env)?;
+        counter.set(counter.get() + 1);
+        Ok((Exp::Atom(Atom::Number(values.len() as f64)), env))
+    });
+
+    assert_eq!(
+        interpreter.eval_str("(count 'a 'b (+ 1 2))")?,
+        Exp::Atom(Atom::Number(3.0))
+    );
+    interpreter.eval_str("(:= c count)")?;
+    assert_eq!(interpreter.eval_str("(c)")?, Exp::Atom(Atom::Number(0.0)));
+    assert_eq!(
+        interpreter.eval_str("(apply count '(a (b c)))")?,
+        Exp::Atom(Atom::Number(2.0))
+    );
+    assert_eq!(calls.get(), 3);
+
+    assert_eq!(interpreter.eval_str("c")?.to_string(), "count");
+
+    // Operators belong to the interpreter they are registered with.
+    let mut fresh = Interpreter::new();
+    assert!(fresh.eval_str("(count 'a)").is_err());
+    assert_eq!(calls.get(), 3);
+    fresh.register_operator("count", |_, env| Ok((Exp::Atom(Atom::Integer(-1)), env)));
+    assert_eq!(fresh.eval_str("(count 'a)")?, Exp::Atom(Atom::Integer(-1)));
+    assert_eq!(
+        interpreter.eval_str("(count 'a)")?,
+        Exp::Atom(Atom::Number(1.0))
+    );
+    assert!(fresh.eval_str("(uncount 'a)").is_err());
+    Ok(())
+}
+
+#[test]
+fn errors() {
+    let mut interpreter = Interpreter::new();
+
+    let err = interpreter.eval_str("(.< 'a)").unwrap_err();
+    assert!(
+        matches!(err.root(), LispicoError::TypeMismatch { expected: "a list", found } if *found == Exp::Atom(Atom::Identifier("a".to_string()))),
+        "{err}"
+    );
+    assert_eq!(err.root().to_string(), "Expected a list, but got a");
+
+    let err = interpreter.eval_str("(.< ())").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "Expected a non-empty list, but got ()"
+    );
+
+    let err = interpreter.eval_str("(/ 1 0)").unwrap_err();
+    assert!(matches!(err.root(), LispicoError::DivisionByZero));
+
+    let err = interpreter.eval_str("(-)").unwrap_err();
+    assert!(matches!(
+        err.root(),
+        LispicoError::ArityMismatch {
+            expected: Arity::AtLeast(1),
+            found: 0,
+            ..
+        }
+    ));
+    let err = interpreter.eval_str("(=)").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "= expects at least 1 argument(s), but got 0"
+    );
+    let err = interpreter.eval_str("(^ 2 3 4)").unwrap_err();
+    assert!(matches!(
+        err.root(),
+        LispicoError::ArityMismatch {
+            expected: Arity::Exactly(2),
+            found: 3,
+            ..
+        }
+    ));
+    let err = interpreter.eval_str("(/ 1 2 0)").unwrap_err();
+    assert!(matches!(err.root(), LispicoError::DivisionByZero));
+    let err = interpreter.eval_str("(% 1 0)").unwrap_err();
+    assert!(matches!(err.root(), LispicoError::DivisionByZero));
+    let err = interpreter.eval_str("(% 1.5 1)").unwrap_err();
+    assert_eq!(err.root().to_string(), "Expected an integer, but got 1.5");
+    let err = interpreter.eval_str("(< 1 'a)").unwrap_err();
+    assert_eq!(err.root().to_string(), "Expected a number, but got a");
+
+    interpreter.eval_str("(:= op 'nope)").unwrap();
+    let err = interpreter.eval_str("(op 1)").unwrap_err();
+    assert!(matches!(err.root(), LispicoError::UnknownOperator(ref name) if name == "nope"));
+
+    let err = interpreter.eval_str("(1 2)").unwrap_err();
+    assert!(matches!(
+        err.root(),
+        LispicoError::TypeMismatch {
+            expected: "an operator",
+            ..
+        }
+    ));
+
+    let err = interpreter.eval_str("(+ 1").unwrap_err();
+    assert!(matches!(err, LispicoError::Parse(_)));
+
+    let err = interpreter
+        .eval_str("(# \"does/not/exist.lc\")")
+        .unwrap_err();
+    assert!(
+        matches!(err.root(), LispicoError::ImportFailed { path, source } if path == "does/not/exist.lc" && matches!(**source, LispicoError::Io(_)))
+    );
+
+    let err = Interpreter::new()
+        .load_file("does/not/exist.lc")
+        .unwrap_err();
+    assert!(
+        matches!(err, LispicoError::ReadFailed { ref path, .. } if path == "does/not/exist.lc")
+    );
+    assert!(err
+        .traceback()
+        .to_string()
+        .starts_with("Error: Failed to read \"does/not/exist.lc\": "));
+}
+
+#[test]
+fn locations() -> Result<()> {
+    let program = "(+ 1\n   (* 2\n      (.< 'a)))";
+    let exp = &parser::parse_source(program, "test.lc", 10)?[0];
+    assert_eq!(
+        exp.location().map(|l| l.to_string()),
+        Some("test.lc:10:1".to_string())
+    );
+
+    let err = exp.eval(get_default_env()).unwrap_err();
+    assert_eq!(
+        err.location().map(|l| l.to_string()),
+        Some("test.lc:12:7".to_string())
+    );
+    assert_eq!(err.to_string(), "test.lc:12:7: Expected a list, but got a");
+
+    // Line 0 is taken as the first line.
+    let exp = &parser::parse_source("(a)", "test.lc", 0)?[0];
+    assert_eq!(exp.location().map(|l| l.line), Some(1));
+    let err = parser::parse_source("(a", "test.lc", 0).unwrap_err();
+    assert!(err.to_string().contains("test.lc:1:3"), "{err}");
+
+    let (quoted, _) = parser::parse("(.< '((b c)))")?.eval(get_default_env())?;
+    assert!(matches!(quoted, Exp::List(_)));
+
+    // Atoms carry no location, as they fail to evaluate only through a malformed environment.
+    // Errors are reported at the calls that led to them, even when the atom is in tail position.
+    let atom = &parser::parse_source("x", "test.lc", 1)?[0];
+    assert!(atom.location().is_none());
+    let env = List::Cons(Rc::new(parser::parse("(x)")?), Rc::new(get_default_env()));
+    assert!(atom.eval(env).unwrap_err().location().is_none());
+    let program = "(:= y 1)\n(eval 'x '((x)))";
+    let err = Interpreter::new().eval_str(program).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "<input>:2:1: Expected a binding, but got (x)"
+    );
+    Ok(())
+}
+
+#[test]
+fn traceback() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(:= f (-> (x) (.< x)))")?;
+    interpreter.eval_str("(:= g (-> (y) (f (+ y 1))))")?;
+
+    let err = interpreter.eval_str("(g 1)").unwrap_err();
+    let calls: Vec<String> = err.trace().iter().map(|frame| frame.to_string()).collect();
+    assert_eq!(
+        calls,
+        vec![
+            "<input>:1:15: (.< x)",
+            "<input>:1:15: (f 2)",
+            "<input>:1:1: (g 1)",
+        ]
+    );
+    assert_eq!(
+        err.traceback().to_string(),
+        "Traceback (most recent call last):\n  <input>:1:1: (g 1)\n  <input>:1:15: (f 2)\n  <input>:1:15: (.< x)\nError: Expected a list, but got 2"
+    );
+    Ok(())
+}
+
+#[test]
+fn stream_errors() -> Result<()> {
+    let input = "(:= x 2)\n(+ x))\n(/ 1 0)\n(:= y (+ x 1))\n";
+
+    let env = modules::execute_stream(input.as_bytes(), "<repl>", get_default_env(), true)?;
+    let (res, _) = parser::parse("(. x (. y ()))")?.eval(env)?;
+    assert_eq!(res.to_string(), "(2 3)");
+
+    let err = modules::execute_stream(input.as_bytes(), "script.lc", get_default_env(), false)
+        .unwrap_err();
+    assert!(matches!(err, LispicoError::Parse(_)));
+    assert!(err.to_string().contains("script.lc:2:6"), "{err}");
+    Ok(())
+}
+
+#[test]
+fn multiline_stream() -> Result<()> {
+    let input = "(:= x\n  (+ 1\n     2))\n\n(:= s \"a\nb\")\n(:= err (.<\n  x))\n";
+    let env = modules::execute_stream(input.as_bytes(), "<repl>", get_default_env(), true)?;
+    let (res, _) = parser::parse("(. x (. s ()))")?.eval(env)?;
+    assert_eq!(res.to_string(), "(3 \"a\\nb\")");
+
+    let err = modules::execute_stream(input.as_bytes(), "script.lc", get_default_env(), false)
+        .unwrap_err();
+    assert_eq!(err.location().map(|l| l.line), Some(7));
+
+    let input = "(:= x 1)\n\n  (+ x\n";
+    let err = modules::execute_stream(input.as_bytes(), "script.lc", get_default_env(), false)
+        .unwrap_err();
+    assert!(matches!(err, LispicoError::UnterminatedExpression(_)));
+    assert_eq!(err.to_string(), "script.lc:3:3: Unterminated expression");
+    Ok(())
+}
+
+#[test]
+fn multiple_forms() -> Result<()> {
+    let exps = parser::parse_program("(:= x 1) (:= y 2)\n  x\n\n(+ x y)")?;
+    assert_eq!(exps.len(), 4);
+    assert_eq!(exps[2], Exp::Atom(Atom::Identifier("x".to_string())));
+    assert_eq!(exps[3].location().map(|l| l.line), Some(4));
+    assert!(parser::parse_program("")?.is_empty());
+    assert!(parser::parse("(a) (b)").is_err());
+
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.eval_str("(:= x 1) (:= y 2) (+ x y)")?,
+        Exp::Atom(Atom::Integer(3))
+    );
+    assert_eq!(interpreter.eval_str("")?, Exp::List(List::Nil));
+
+    let input = "(:= a 1) (:= b (+ a 1))\n(:= c (.< a)) (:= d 4)\n";
+    let env = modules::execute_stream(input.as_bytes(), "<repl>", get_default_env(), true)?;
+    let (res, _) = parser::parse("(. a (. b (. c (. d ()))))")?.eval(env)?;
+    assert_eq!(res.to_string(), "(1 2 () ())");
+    Ok(())
+}
+
+#[test]
+fn comments() -> Result<()> {
+    let cases = vec![
+        ("(a ; comment\n b)", "(a b)"),
+        ("(a ;| (block) ;| nested |; |; b)", "(a b)"),
+        ("(a ;_(b (c)) d)", "(a d)"),
+        ("(a ;_b ;_c d)", "(a d)"),
+        ("(a ;_ note (b)\n c)", "(a c)"),
+        ("(a ;_'b c)", "(a c)"),
+        ("(;_a)", "()"),
+    ];
+    for (program, expected) in cases {
+        assert_eq!(
+            parser::parse(program)?.to_string(),
+            expected,
+            "program: {program}"
+        );
+    }
+
+    let input = "(:= x ; the answer (\n  42) ;| (:= x 0)\n |;\n(:= y ;_(/ 1 0) x)\n";
+    let env = modules::execute_stream(input.as_bytes(), "<repl>", get_default_env(), false)?;
+    let (res, _) = parser::parse("(. x (. y ()))")?.eval(env)?;
+    assert_eq!(res.to_string(), "(42 42)");
+
+    // A line comment starting with `_` hides nothing and runs nothing.
+    let input = "(:= x 1)\n;_ disabled: (:= x 2)\n;_ TODO (/ 1 0)\n;_\n";
+    let env = modules::execute_stream(input.as_bytes(), "<repl>", get_default_env(), false)?;
+    let (res, _) = parser::parse("x")?.eval(env)?;
+    assert_eq!(res.to_string(), "1");
+
+    let input = ";| header\n(:= x 0)\n|;\n(:= x 1)\n";
+    let env = modules::execute_stream(input.as_bytes(), "<repl>", get_default_env(), false)?;
+    let (res, _) = parser::parse("x")?.eval(env)?;
+    assert_eq!(res.to_string(), "1");
+
+    // An unclosed block comment is an error, and not a line comment, whether read as a whole or
+    // line by line.
+    let input = ";|---- header ----\n(:= x 1)\n";
+    assert!(parser::parse_source(input, "t1.lc", 1).is_err());
+    let err =
+        modules::execute_stream(input.as_bytes(), "t1.lc", get_default_env(), false).unwrap_err();
+    assert!(
+        matches!(err, LispicoError::UnterminatedExpression(_)),
+        "{err}"
+    );
+    Ok(())
+}
+
+#[test]
+fn strings() -> Result<()> {
+    let cases = vec![
+        (r#""plain""#, "plain"),
+        (r#""say \"hi\"""#, "say \"hi\""),
+        (r#""a\nb\tc\rd\0e\\f""#, "a\nb\tc\rd\0e\\f"),
+        (r#""\u{48}\u{e9}\u{1F600}""#, "H\u{e9}\u{1F600}"),
+        (
+            r#""""raw \n "quoted" \ text""""#,
+            r#"raw \n "quoted" \ text"#,
+        ),
+        ("\"\"\"\nfirst\nsecond\n\"\"\"", "first\nsecond\n"),
+        (r#""""""""#, ""),
+        (r#""  padded  ""#, "  padded  "),
+        (r#"" ; not a comment""#, " ; not a comment"),
+        (r#"";| not |; a block""#, ";| not |; a block"),
+        ("\"\"\"\n  indented ;x\"\"\"", "  indented ;x"),
+    ];
+    for (program, expected) in cases {
+        assert_eq!(
+            parser::parse(program)?,
+            Exp::Atom(Atom::String(expected.to_string())),
+            "program: {program}"
+        );
+    }
+
+    let strings = vec![
+        "",
+        "plain",
+        "quote \" and backslash \\",
+        "lines\nand\ttabs\r\0",
+        "bell \u{7} and escape \u{1b}",
+        "unicode gr\u{f6}\u{df}e \u{1F600}",
+        r#"\n is not a newline"#,
+        " leading and trailing ",
+        ";x",
+        "\t;_ (a) ;| b |;",
+    ];
+    for s in strings {
+        let atom = Exp::Atom(Atom::String(s.to_string()));
+        let printed = atom.to_string();
+        assert_eq!(parser::parse(&printed)?, atom, "printed: {printed}");
+    }
+    assert_eq!(
+        Exp::Atom(Atom::String("a\"b\nc".to_string())).to_string(),
+        r#""a\"b\nc""#
+    );
+
+    let input = "(:= s \"(\\\"\")\n(:= r \"\"\"\n)\"\n\"\"\")\n(:= u (. s (. r ())))\n";
+    let env = modules::execute_stream(input.as_bytes(), "<repl>", get_default_env(), false)?;
+    let (res, _) = parser::parse("u")?.eval(env)?;
+    assert_eq!(res.to_string(), r#"("(\"" ")\"\n")"#);
+    Ok(())
+}
+
+#[test]
+fn identifiers_and_numbers() -> Result<()> {
+    let exp = parser::parse("(x1 -5 1.5 -x - empty? größe ->str -0.25)")?;
+    let expected = vec![
+        Exp::Atom(Atom::Identifier("x1".to_string())),
+        Exp::Atom(Atom::Integer(-5)),
+        Exp::Atom(Atom::Number(1.5)),
+        Exp::Atom(Atom::Identifier("-x".to_string())),
+        Exp::Atom(Atom::Identifier("-".to_string())),
+        Exp::Atom(Atom::Identifier("empty?".to_string())),
+        Exp::Atom(Atom::Identifier("größe".to_string())),
+        Exp::Atom(Atom::Identifier("->str".to_string())),
+        Exp::Atom(Atom::Number(-0.25)),
+    ];
+    assert_eq!(exp.as_list()?.iter().cloned().collect::<Vec<_>>(), expected);
+    assert!(parser::parse("(1a)").is_err());
+    assert!(parser::parse("(1.)").is_err());
+    assert!(parser::parse("(1e)").is_err());
+    assert!(parser::parse("(1.5e2.0)").is_err());
+
+    // Floats are printed so that they read back as the same value.
+    let floats = vec![
+        0.1,
+        -0.0,
+        1e20,
+        -2.5e300,
+        1.5e-7,
+        f64::MIN_POSITIVE,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ];
+    for num in floats {
+        let printed = Exp::Atom(Atom::Number(num)).to_string();
+        let read = parser::parse(&printed)?;
+        assert_eq!(read.to_string(), printed);
+        assert!(matches!(read, Exp::Atom(Atom::Number(_))), "{printed}");
+    }
+    assert_eq!(parser::parse("1.5e2")?, Exp::Atom(Atom::Number(150.0)));
+    assert_eq!(parser::parse("2e-1")?, Exp::Atom(Atom::Number(0.2)));
+    assert_eq!(parser::parse("+5")?, Exp::Atom(Atom::Integer(5)));
+    assert_eq!(parser::parse(".5")?, Exp::Atom(Atom::Number(0.5)));
+    assert_eq!(parser::parse("-.5e1")?, Exp::Atom(Atom::Number(-5.0)));
+    assert_eq!(parser::parse("+1/2")?.to_string(), "1/2");
+    assert_eq!(
+        parser::parse(".5a")?,
+        Exp::Atom(Atom::Identifier(".5a".to_string()))
+    );
+    assert_eq!(
+        parser::parse("+inf")?,
+        Exp::Atom(Atom::Identifier("+inf".to_string()))
+    );
+
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.eval_str("(:= x1 2) (:= größe 3) (* x1 größe)")?,
+        Exp::Atom(Atom::Integer(6))
+    );
+    Ok(())
+}
+
+#[test]
+fn closures() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(:= make-adder (-> (n) (-> (x) (+ x n))))")?;
+    interpreter.eval_str("(:= add2 (make-adder 2)) (:= add10 (make-adder 10))")?;
+    assert_eq!(
+        interpreter
+            .eval_str("(. (add2 1) (. (add10 1) ()))")?
+            .to_string(),
+        "(3 11)"
+    );
+
+    // A function sees the bindings of its definition, not of its caller.
+    interpreter.eval_str("(:= n 100)")?;
+    assert_eq!(
+        interpreter.eval_str("(add2 1)")?,
+        Exp::Atom(Atom::Integer(3))
+    );
+    interpreter.eval_str("(:= get-y (-> () y)) (:= y 1)")?;
+    assert_eq!(
+        interpreter.eval_str("({} ((y 2)) (get-y))")?,
+        Exp::List(List::Nil)
+    );
+
+    interpreter.eval_str("(:= scaler ({} ((k 3)) (-> (x) (* k x))))")?;
+    assert_eq!(
+        interpreter.eval_str("(scaler 5)")?,
+        Exp::Atom(Atom::Integer(15))
+    );
+
+    interpreter.eval_str("(:= compose (-> (f g) (-> (x) (f (g x)))))")?;
+    assert_eq!(
+        interpreter.eval_str("((compose add10 scaler) 2)")?,
+        Exp::Atom(Atom::Integer(16))
+    );
+
+    // Data that merely looks like a function is not callable.
+    let err = interpreter
+        .eval_str("(:= fake '(-> (x) x)) (fake 1)")
+        .unwrap_err();
+    assert!(matches!(
+        err.root(),
+        LispicoError::TypeMismatch {
+            expected: "an operator",
+            ..
+        }
+    ));
+
+    assert_eq!(
+        interpreter.eval_str("add2")?.to_string(),
+        "(-> (x) (+ x n))"
+    );
+    assert_eq!(interpreter.eval_str("(= add2 add2)")?.to_string(), "t");
+    assert_eq!(
+        interpreter.eval_str("(= add2 add10)")?,
+        Exp::List(List::Nil)
+    );
+    Ok(())
+}
+
+#[test]
+fn recursion() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(::= fact (-> (n) (? (= n 0) 1 (* n (fact (- n 1))))))")?;
+    assert_eq!(
+        interpreter.eval_str("(fact 10)")?,
+        Exp::Atom(Atom::Integer(3628800))
+    );
+
+    interpreter.eval_str(
+        "(::=
+            even? (-> (n) (? (= n 0) 't (odd? (- n 1))))
+            odd? (-> (n) (? (= n 0) () (even? (- n 1)))))",
+    )?;
+    assert_eq!(interpreter.eval_str("(even? 10)")?.to_string(), "t");
+    assert_eq!(interpreter.eval_str("(odd? 10)")?, Exp::List(List::Nil));
+    assert_eq!(interpreter.eval_str("(odd? 7)")?.to_string(), "t");
+
+    interpreter.eval_str(
+        "(:= tree
+            '(node (node (leaf 1) (leaf 2))
+                   (node (leaf 3) (node (leaf 4) (leaf 5)))))",
+    )?;
+    let sum = "({{}} ((value (-> (node) (.< (.> node))))
+                     (left (-> (node) (.< (.> node))))
+                     (right (-> (node) (.< (.> (.> node)))))
+                     (sum (-> (node)
+                       (? (= (.< node) 'leaf)
+                          (value node)
+                          (+ (sum (left node)) (sum (right node)))))))
+                 (sum tree))";
+    assert_eq!(interpreter.eval_str(sum)?, Exp::Atom(Atom::Integer(15)));
+
+    // Local groups do not leak, and see the values defined alongside them.
+    assert_eq!(interpreter.eval_str("sum")?, Exp::List(List::Nil));
+    assert_eq!(
+        interpreter.eval_str(
+            "({{}} ((base 2)
+                    (pow (-> (n) (? (= n 0) 1 (* base (pow (- n 1)))))))
+                (pow 10))"
+        )?,
+        Exp::Atom(Atom::Integer(1024))
+    );
+
+    // Functions defined with `:=` see their own name, but other values capture the environment
+    // before it is bound.
+    interpreter.eval_str("(:= countdown (-> (n) (? (= n 0) 'done (countdown (- n 1)))))")?;
+    assert_eq!(interpreter.eval_str("(countdown 3)")?.to_string(), "done");
+    interpreter.eval_str("(:= f (-> () (f)))")?;
+    interpreter.eval_str("(:= f ({} ((g f)) (-> () g)))")?;
+    assert_eq!(interpreter.eval_str("(f)")?.to_string(), "(-> () (f))");
+    interpreter.eval_str("(:= stray ({} () (-> (n) (stray n))))")?;
+    assert!(interpreter.eval_str("(stray 3)").is_err());
+    Ok(())
+}
+
+#[test]
+fn tail_calls() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(::= count (-> (n acc) (? (= n 0) acc (count (- n 1) (+ acc 1)))))")?;
+    assert_eq!(
+        interpreter.eval_str("(count 1000000 0)")?,
+        Exp::Atom(Atom::Integer(1000000))
+    );
+
+    // Mutual recursion, and tail calls out of the body of a scope.
+    interpreter.eval_str(
+        "(::=
+            ping (-> (n) ({} ((m (- n 1))) (? (= m 0) 'ping (pong m))))
+            pong (-> (n) ({{}} ((m (- n 1))) (? (= m 0) 'pong (ping m)))))",
+    )?;
+    assert_eq!(interpreter.eval_str("(ping 10000)")?.to_string(), "pong");
+
+    // Long lists built in tail position are freed without overflowing the stack.
+    let long: List = (0..1_000_000)
+        .map(|n| Exp::Atom(Atom::Integer(n)))
+        .collect();
+    assert_eq!(long.len(), 1_000_000);
+    drop(long);
+    interpreter.eval_str("(::= build (-> (n acc) (? (= n 0) acc (build (- n 1) (. n acc)))))")?;
+    interpreter.eval_str("(:= built (build 100000 ()))")?;
+    assert_eq!(
+        interpreter.eval_str("(.< built)")?,
+        Exp::Atom(Atom::Integer(1))
+    );
+
+    // Scopes still end once their body is evaluated.
+    interpreter.eval_str("(:= x 1)")?;
+    interpreter.eval_str("({} ((x 2)) (:= y x))")?;
+    assert_eq!(interpreter.eval_str("x")?, Exp::Atom(Atom::Integer(1)));
+    assert_eq!(interpreter.eval_str("y")?, Exp::List(List::Nil));
+    Ok(())
+}
+
+#[test]
+fn function_params() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(:= pair (-> (a b) (. a (. b ()))))")?;
+
+    let err = interpreter.eval_str("(pair 1)").unwrap_err();
+    assert!(matches!(
+        err.root(),
+        LispicoError::ArityMismatch { operator, expected: Arity::Exactly(2), found: 1 } if operator == "pair"
+    ));
+    assert_eq!(
+        err.root().to_string(),
+        "pair expects 2 argument(s), but got 1"
+    );
+    let err = interpreter.eval_str("(pair 1 2 3)").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "pair expects 2 argument(s), but got 3"
+    );
+    let err = interpreter.eval_str("((-> () 1) 2)").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "(-> () 1) expects 0 argument(s), but got 1"
+    );
+
+    interpreter.eval_str("(:= list (-> (. items) items))")?;
+    assert_eq!(interpreter.eval_str("(list)")?, Exp::List(List::Nil));
+    assert_eq!(
+        interpreter.eval_str("(list 1 (+ 1 1) 3)")?.to_string(),
+        "(1 2 3)"
+    );
+    interpreter.eval_str("(:= tagged (-> (tag . items) (. tag items)))")?;
+    assert_eq!(
+        interpreter.eval_str("(tagged 'a 1 2)")?.to_string(),
+        "(a 1 2)"
+    );
+    let err = interpreter.eval_str("(tagged)").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "tagged expects at least 1 argument(s), but got 0"
+    );
+
+    // Defaults are evaluated on each call, and see the parameters before them.
+    interpreter
+        .eval_str("(:= range (-> (from (to (+ from 10)) (step 1)) (. from (. to (. step ())))))")?;
+    assert_eq!(interpreter.eval_str("(range 1)")?.to_string(), "(1 11 1)");
+    assert_eq!(interpreter.eval_str("(range 1 5)")?.to_string(), "(1 5 1)");
+    assert_eq!(
+        interpreter.eval_str("(range 1 5 2)")?.to_string(),
+        "(1 5 2)"
+    );
+    let err = interpreter.eval_str("(range 1 2 3 4)").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "range expects 1 to 3 argument(s), but got 4"
+    );
+    interpreter.eval_str("(:= opt-rest (-> (a (b 'none) . c) (. a (. b (. c ())))))")?;
+    assert_eq!(
+        interpreter.eval_str("(opt-rest 1)")?.to_string(),
+        "(1 none ())"
+    );
+    assert_eq!(
+        interpreter.eval_str("(opt-rest 1 2 3 4)")?.to_string(),
+        "(1 2 (3 4))"
+    );
+
+    for malformed in [
+        "(-> (a .) a)",
+        "(-> (. a b) a)",
+        "(-> ((a 1) b) a)",
+        "(-> ((a)) a)",
+    ] {
+        assert!(interpreter.eval_str(malformed).is_err(), "{malformed}");
+    }
+    Ok(())
+}
+
+#[test]
+fn integers_and_floats() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    let programs = vec![
+        ("3", "3"),
+        ("3.0", "3.0"),
+        ("(+ 0.5 0.5)", "1.0"),
+        ("(+ 1 2.0)", "3.0"),
+        ("(* 2 3)", "6"),
+        ("(/ 7 2)", "7/2"),
+        ("(/ 8 2 2)", "2"),
+        ("(// 7 2)", "3"),
+        ("(// -7 2)", "-4"),
+        ("(// 7.5 2)", "3.0"),
+        ("(^ 2 10)", "1024"),
+        ("(^ 2 -1)", "1/2"),
+        ("(^ 4 0.5)", "2.0"),
+        ("(< 1 1.5 2)", "t"),
+        ("(int 2.9)", "2"),
+        ("(int -2.9)", "-2"),
+        ("(int 7)", "7"),
+        ("(float 7)", "7.0"),
+        ("9007199254740993", "9007199254740993"),
+        ("(+ 9007199254740992 1)", "9007199254740993"),
+    ];
+    for (program, expected) in programs {
+        assert_eq!(
+            interpreter.eval_str(program)?.to_string(),
+            expected,
+            "program: {program}"
+        );
+    }
+
+    assert_ne!(interpreter.eval_str("1")?, interpreter.eval_str("1.0")?);
+    let err = interpreter.eval_str("(% 7.0 2)").unwrap_err();
+    assert_eq!(err.root().to_string(), "Expected an integer, but got 7.0");
+    let err = interpreter.eval_str("(// 1 0)").unwrap_err();
+    assert!(matches!(err.root(), LispicoError::DivisionByZero));
+    let err = interpreter.eval_str("(int (^ 10.0 400))").unwrap_err();
+    assert!(err.to_string().contains("Cannot convert"), "{err}");
+    Ok(())
+}
+
+#[test]
+fn exact_numbers() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    let programs = vec![
+        ("(/ 1 3)", "1/3"),
+        ("(+ 1/3 1/6)", "1/2"),
+        ("(* 2/3 3/2)", "1"),
+        ("2/4", "1/2"),
+        ("-6/3", "-2"),
+        ("(/ 3/4)", "4/3"),
+        ("(- 1/2)", "-1/2"),
+        ("(+ 1/2 0.25)", "0.75"),
+        ("(// 7/2 1)", "3"),
+        ("(^ 2/3 2)", "4/9"),
+        ("(^ 2/3 -2)", "9/4"),
+        ("(^ 4 1/2)", "2.0"),
+        ("(int -7/2)", "-3"),
+        ("(float 1/4)", "0.25"),
+        ("(* 9223372036854775807 2)", "18446744073709551614"),
+        ("(+ 9223372036854775807 1)", "9223372036854775808"),
+        ("(- -9223372036854775808 1)", "-9223372036854775809"),
+        ("(- 9223372036854775808 1)", "9223372036854775807"),
+        ("(^ 2 100)", "1267650600228229401496703205376"),
+        ("(/ (^ 2 100) (^ 2 98))", "4"),
+        ("(% (^ 10 20) 7)", "2"),
+        ("(%% (- (^ 10 20)) 7)", "5"),
+        (
+            "123456789012345678901234567890",
+            "123456789012345678901234567890",
+        ),
+        ("(int (^ 10.0 20))", "100000000000000000000"),
+        ("(= 1 1.0)", "t"),
+        ("(= 1/2 0.5)", "t"),
+        ("(= 2/2 1)", "t"),
+        ("(= 1/3 0.3)", "()"),
+        ("(= (^ 2 64) (* (^ 2 32) (^ 2 32)))", "t"),
+        ("(< 1/3 0.34 1/2 1)", "t"),
+        ("(> (^ 2 64) 1.0)", "t"),
+        ("(!= 1/2 0.5)", "()"),
+        ("(= '(1 (1/2 a)) '(1.0 (0.5 a)))", "t"),
+        ("(= '(1 (2)) '(1 (2.5)))", "()"),
+        ("(^ 1 9999999999)", "1"),
+        ("(^ -1 9999999999)", "-1"),
+        ("(^ -1 -10000000000)", "1"),
+        ("(^ 0 9999999999)", "0"),
+    ];
+    for (program, expected) in programs {
+        assert_eq!(
+            interpreter.eval_str(program)?.to_string(),
+            expected,
+            "program: {program}"
+        );
+    }
+
+    // Results are stored in the smallest representation that holds them.
+    assert_eq!(
+        interpreter.eval_str("(/ 4 2)")?,
+        Exp::Atom(Atom::Integer(2))
+    );
+    assert_eq!(
+        interpreter.eval_str("(- (^ 2 64) (^ 2 64))")?,
+        Exp::Atom(Atom::Integer(0))
+    );
+
+    for program in ["(/ 1/2 0)", "(/ 0/5)", "(^ 0 -1)", "(% (^ 2 64) 0)"] {
+        let err = interpreter.eval_str(program).unwrap_err();
+        assert!(
+            matches!(err.root(), LispicoError::DivisionByZero),
+            "{program}"
+        );
+    }
+    for program in ["(^ 2 9999999999)", "(^ 1/2 -9999999999)"] {
+        let err = interpreter.eval_str(program).unwrap_err();
+        assert!(
+            err.root()
+                .to_string()
+                .contains("too large for an exact power"),
+            "{program}"
+        );
+    }
+    let err = interpreter.eval_str("(% 1/2 2)").unwrap_err();
+    assert_eq!(err.root().to_string(), "Expected an integer, but got 1/2");
+    assert!(parser::parse("1/0").is_err());
+    Ok(())
+}
+
+#[test]
+fn logical_operators() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    let programs = vec![
+        ("(and)", "t"),
+        ("(or)", "()"),
+        ("(and 1 2 3)", "3"),
+        ("(and 1 () 3)", "()"),
+        ("(or () 2 3)", "2"),
+        ("(or () ())", "()"),
+        ("(not ())", "t"),
+        ("(not 0)", "()"),
+        ("(and (< 1 2) (not (= 1 2)))", "t"),
+        ("t", "t"),
+    ];
+    for (program, expected) in programs {
+        assert_eq!(
+            interpreter.eval_str(program)?.to_string(),
+            expected,
+            "program: {program}"
+        );
+    }
+
+    // Arguments after the deciding one are not evaluated.
+    assert_eq!(
+        interpreter.eval_str("(and () (.< ()))")?,
+        Exp::List(List::Nil)
+    );
+    assert_eq!(interpreter.eval_str("(or 1 (.< ()))")?.to_string(), "1");
+    assert_eq!(interpreter.eval_str("(or (:= x 1) x)")?.to_string(), "1");
+
+    // The last argument is in tail position.
+    interpreter
+        .eval_str("(::= all-even (-> (n) (or (= n 0) (and (not (= n 1)) (all-even (- n 2))))))")?;
+    assert_eq!(interpreter.eval_str("(all-even 100000)")?.to_string(), "t");
+
+    // The true value cannot be rebound.
+    for program in [
+        "(:= t ())",
+        "(::= t 1)",
+        "({} ((t ())) t)",
+        "(-> (t) t)",
+        "(-> ((t 1)) t)",
+        "(-> (. t) t)",
+    ] {
+        assert!(interpreter.eval_str(program).is_err(), "{program}");
+    }
+    assert_eq!(interpreter.eval_str("t")?.to_string(), "t");
+    Ok(())
+}
+
+#[test]
+fn sequencing() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.eval_str("(do)")?, Exp::List(List::Nil));
+    assert_eq!(
+        interpreter
+            .eval_str("(do (:= x 1) (:= y (+ x 1)) (. x (. y ())))")?
+            .to_string(),
+        "(1 2)"
+    );
+    // Definitions made in sequence stay in the enclosing environment.
+    assert_eq!(interpreter.eval_str("y")?, Exp::Atom(Atom::Integer(2)));
+
+    // Function and scope bodies are sequences, whose definitions stay local.
+    interpreter.eval_str(
+        "(:= hypot (-> (a b)
+            (:= square (-> (n) (* n n)))
+            (+ (square a) (square b))))",
+    )?;
+    assert_eq!(
+        interpreter.eval_str("(hypot 3 4)")?,
+        Exp::Atom(Atom::Integer(25))
+    );
+    assert_eq!(interpreter.eval_str("square")?, Exp::List(List::Nil));
+    assert_eq!(
+        interpreter.eval_str("({} ((a 1)) (:= b (+ a 1)) (* b 10))")?,
+        Exp::Atom(Atom::Integer(20))
+    );
+    assert_eq!(interpreter.eval_str("b")?, Exp::List(List::Nil));
+    assert_eq!(
+        interpreter.eval_str("({{}} ((f (-> () 1))) (:= g f) (g))")?,
+        Exp::Atom(Atom::Integer(1))
+    );
+    assert_eq!(interpreter.eval_str("((-> ()))")?, Exp::List(List::Nil));
+    assert_eq!(
+        interpreter.eval_str("hypot")?.to_string(),
+        "(-> (a b) (:= square (-> (n) (* n n))) (+ (square a) (square b)))"
+    );
+
+    // The last form is in tail position.
+    interpreter.eval_str("(::= loop (-> (n) (:= m (- n 1)) (? (= m 0) 'done (loop m))))")?;
+    assert_eq!(interpreter.eval_str("(loop 100000)")?.to_string(), "done");
+    Ok(())
+}
+
+#[test]
+fn macros() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(:= unless (macro (c then else) (. '? (. c (. else (. then ()))))))")?;
+    assert_eq!(interpreter.eval_str("(unless () 'a 'b)")?.to_string(), "a");
+    // Only the chosen branch is evaluated.
+    assert_eq!(
+        interpreter.eval_str("(unless 't (.< ()) 'b)")?.to_string(),
+        "b"
+    );
+    assert_eq!(
+        interpreter
+            .eval_str("(macroexpand-1 '(unless x 1 2))")?
+            .to_string(),
+        "(? x 2 1)"
+    );
+    assert_eq!(
+        interpreter
+            .eval_str("(macroexpand-1 '(+ 1 2))")?
+            .to_string(),
+        "(+ 1 2)"
+    );
+
+    // Expansions are evaluated in the caller's environment.
+    interpreter.eval_str("(:= define (macro (name value) (. ':= (. name (. value ())))))")?;
+    interpreter.eval_str("(define x (+ 1 2))")?;
+    assert_eq!(interpreter.eval_str("x")?, Exp::Atom(Atom::Integer(3)));
+    interpreter.eval_str("(:= when (macro (c . body) (. '? (. c (. (. 'do body) ())))))")?;
+    assert_eq!(
+        interpreter.eval_str("({} ((y 1)) (when (= y 1) (:= z (+ y 1)) (* z 10)))")?,
+        Exp::Atom(Atom::Integer(20))
+    );
+
+    // Macros may expand into calls to themselves, or to other macros.
+    interpreter.eval_str(
+        "(:= all (macro (. forms)
+            (? forms
+               (. '? (. (.< forms) (. (. 'all (.> forms)) (. () ()))))
+               't)))",
+    )?;
+    assert_eq!(interpreter.eval_str("(all 1 2 3)")?.to_string(), "t");
+    assert_eq!(
+        interpreter.eval_str("(all 1 () (.< ()))")?,
+        Exp::List(List::Nil)
+    );
+    interpreter.eval_str("(:= define-one (macro (name) (. 'define (. name (. 1 ())))))")?;
+    assert_eq!(
+        interpreter
+            .eval_str("(macroexpand-1 '(define-one a))")?
+            .to_string(),
+        "(define a 1)"
+    );
+    assert_eq!(
+        interpreter
+            .eval_str("(macroexpand '(define-one a))")?
+            .to_string(),
+        "(:= a 1)"
+    );
+
+    // Transformers defined together see each other and themselves.
+    interpreter.eval_str(
+        "(::=
+            twice-form (-> (form) (. 'do (. form (. form ()))))
+            twice (macro (form) (twice-form form))
+            unfold (macro (n)
+              (? (= n 0) 0 (. '+ (. 1 (. (macroexpand-1 (. 'unfold (. (- n 1) ()))) ()))))))",
+    )?;
+    assert_eq!(
+        interpreter.eval_str("({} ((n 0)) (twice (:= n (+ n 1))) n)")?,
+        Exp::Atom(Atom::Integer(2))
+    );
+    assert_eq!(
+        interpreter
+            .eval_str("(macroexpand-1 '(unfold 2))")?
+            .to_string(),
+        "(+ 1 (+ 1 0))"
+    );
+    assert_eq!(
+        interpreter.eval_str("(unfold 3)")?,
+        Exp::Atom(Atom::Integer(3))
+    );
+
+    // Identifiers made by gensym are fresh.
+    let a = interpreter.eval_str("(gensym)")?;
+    let b = interpreter.eval_str("(gensym)")?;
+    assert_ne!(a, b);
+    // Nor can they be written in source, and so bound there beforehand.
+    for n in 1..=4 {
+        interpreter.eval_str(&format!("(:= g#{n} 5) (:= g{n} 5) (:= g-{n} 5)"))?;
+    }
+    for _ in 0..4 {
+        let name = interpreter.eval_str("(gensym)")?.to_string();
+        assert!(parser::parse(&name).is_err(), "{name}");
+        assert_eq!(
+            interpreter.eval_str(&format!("(eval (string->symbol \"{name}\"))"))?,
+            Exp::List(List::Nil)
+        );
+    }
+    assert!(interpreter
+        .eval_str("(gensym \"tmp\")")?
+        .to_string()
+        .starts_with("tmp"));
+    interpreter.eval_str(
+        "(:= swap-pair (macro (pair)
+            ({} ((tmp (gensym)))
+              (. '{} (. (. (. tmp (. pair ())) ())
+                        (. (. '. (. (. '.< (. (. '.> (. tmp ())) ()))
+                                    (. (. '. (. (. '.< (. tmp ())) (. () ()))) ())))
+                           ()))))))",
+    )?;
+    assert_eq!(
+        interpreter
+            .eval_str("({} ((tmp '(1 2))) (swap-pair tmp))")?
+            .to_string(),
+        "(2 1)"
+    );
+
+    let err = interpreter.eval_str("(unless 1 2)").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "unless expects 3 argument(s), but got 2"
+    );
+    assert_eq!(
+        interpreter.eval_str("(macro (a) a)")?.to_string(),
+        "(macro (a) a)"
+    );
+    Ok(())
+}
+
+#[test]
+fn quasiquote() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(:= x 1)")?;
+    interpreter.eval_str("(:= xs '(2 3))")?;
+    let programs = vec![
+        ("`a", "a"),
+        ("`(a b)", "(a b)"),
+        ("`(a ,x)", "(a 1)"),
+        ("`(a ,(+ x 1) ,@xs b)", "(a 2 2 3 b)"),
+        ("`(,@xs ,@() ,@xs)", "(2 3 2 3)"),
+        ("`(a (b ,x) ())", "(a (b 1) ())"),
+        ("`(a '(b ,x))", "(a '(b 1))"),
+        ("`(a ,'(b c))", "(a (b c))"),
+        ("`(a `(b ,x))", "(a `(b ,x))"),
+        ("`,x", "1"),
+    ];
+    for (program, expected) in programs {
+        assert_eq!(
+            interpreter.eval_str(program)?.to_string(),
+            expected,
+            "{program}"
+        );
+    }
+    assert!(interpreter.eval_str("`(a ,@x)").is_err());
+
+    // Quoted templates are printed back as they are written.
+    let templates = vec![
+        "(`(a ,b ,@c))",
+        "(`(a '(b ,c) ()))",
+        "(`(,@a) `(a . ,b))",
+        "(`(a `(b ,c)))",
+    ];
+    for template in templates {
+        assert_eq!(
+            interpreter.eval_str(&format!("'{template}"))?.to_string(),
+            template
+        );
+    }
+
+    interpreter.eval_str("(:= unless (macro (c then else) `(? ,c ,else ,then)))")?;
+    assert_eq!(interpreter.eval_str("(unless () 'a 'b)")?.to_string(), "a");
+    interpreter.eval_str("(:= when (macro (c . body) `(? ,c (do ,@body))))")?;
+    assert_eq!(
+        interpreter
+            .eval_str("(macroexpand-1 '(when (= x 1) (:= y 2) (* y 10)))")?
+            .to_string(),
+        "(? (= x 1) (do (:= y 2) (* y 10)))"
+    );
+    assert_eq!(
+        interpreter.eval_str("(when (= x 1) (:= y 2) (* y 10))")?,
+        Exp::Atom(Atom::Integer(20))
+    );
+    Ok(())
+}
+
+#[test]
+fn runtime_evaluation() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(:= x 2)")?;
+    let programs = vec![
+        ("(eval '(+ x 3))", Exp::Atom(Atom::Integer(5))),
+        ("(eval (. '* '(x 4)))", Exp::Atom(Atom::Integer(8))),
+        ("(eval 7)", Exp::Atom(Atom::Integer(7))),
+        (
+            "(eval '(.< '(a b)))",
+            Exp::Atom(Atom::Identifier("a".to_string())),
+        ),
+        ("(eval 'x '((x 10)))", Exp::Atom(Atom::Integer(10))),
+        (
+            "(eval '(+ x y) '((x 10) (y 1) (+ +)))",
+            Exp::Atom(Atom::Integer(11)),
+        ),
+        ("(apply + '(1 2 3))", Exp::Atom(Atom::Integer(6))),
+        ("(apply + ())", Exp::Atom(Atom::Integer(0))),
+        (
+            "(apply (-> (a b) (- a b)) '(5 3))",
+            Exp::Atom(Atom::Integer(2)),
+        ),
+        ("(apply '. '(a (b)))", parser::parse("(a b)")?),
+        (
+            "(apply (-> (x . xs) xs) '((+ 1 2) a))",
+            parser::parse("(a)")?,
+        ),
+        ("(apply '? '(() (/ 1 0) b))", parser::parse("b")?),
+        ("(read-string \"(+ x 1)\")", parser::parse("(+ x 1)")?),
+        (
+            "(eval (read-string \"(+ x 1)\"))",
+            Exp::Atom(Atom::Integer(3)),
+        ),
+        (
+            "(read-string \"\\\"a\\\"\")",
+            Exp::Atom(Atom::String("a".to_string())),
+        ),
+        (
+            "(read-string \"'a\")",
+            Exp::List(
+                vec![
+                    Exp::Atom(Atom::Identifier("'".to_string())),
+                    Exp::Atom(Atom::Identifier("a".to_string())),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        ),
+    ];
+    for (program, expected) in programs {
+        assert_eq!(interpreter.eval_str(program)?, expected, "{program}");
+    }
+
+    // Printed values read back as the same value.
+    interpreter.eval_str("(:= xs '(1 2))")?;
+    let printed = vec!["'a", "'(a 'b)", "`(a ,x ,@xs)", "(x `(,x b) 'c)"];
+    for text in printed {
+        let value = interpreter.eval_str(&format!("(read-string \"{text}\")"))?;
+        assert_eq!(value.to_string(), text);
+    }
+    assert_eq!(
+        interpreter
+            .eval_str("(eval (read-string \"`(a ,x ,@xs)\"))")?
+            .to_string(),
+        "(a 2 1 2)"
+    );
+
+    // Definitions made by an evaluated form stay in the environment it is evaluated in.
+    interpreter.eval_str("(eval '(:= y 1))")?;
+    assert_eq!(interpreter.eval_str("y")?, Exp::Atom(Atom::Integer(1)));
+    interpreter.eval_str("(eval '(:= z 1) '((:= :=)))")?;
+    assert_eq!(interpreter.eval_str("z")?, Exp::List(List::Nil));
+
+    let err = interpreter.eval_str("(eval 'x '((x)))").unwrap_err();
+    assert_eq!(err.root().to_string(), "Expected a binding, but got (x)");
+    let err = interpreter
+        .eval_str("(apply (operative (x) e x) '(1))")
+        .unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "Cannot apply (operative (x) e x), which takes forms rather than values"
+    );
+
+    let faulty_programs = vec![
+        "(eval 'x 1)",
+        "(eval '(+ x 1) '((x 1)))",
+        "(eval 'x '((x)))",
+        "(eval '(+ 1 y) '((+ +) (y)))",
+        "(apply + 1)",
+        "(apply 1 '(2))",
+        "(apply (-> (a) a) '(1 2))",
+        "(apply (operative (x) e x) '(1))",
+        "(apply (macro (x) x) '(1))",
+        "(apply ':= '(a 1))",
+        "(read-string 'a)",
+        "(read-string \"(a\")",
+        "(read-string \"a b\")",
+    ];
+    for program in faulty_programs {
+        assert!(interpreter.eval_str(program).is_err(), "{program}");
+    }
+    Ok(())
+}
+
+#[test]
+fn operatives() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(:= x 1)")?;
+    interpreter.eval_str("(:= quote-all (operative (. forms) env forms))")?;
+    assert_eq!(
+        interpreter.eval_str("(quote-all (+ x 1) y)")?,
+        parser::parse("((+ x 1) y)")?
+    );
+    interpreter.eval_str("(:= current-env (operative () env env))")?;
+    assert_eq!(
+        interpreter.eval_str("({} ((y 5)) (eval '(+ x y) (current-env)))")?,
+        Exp::Atom(Atom::Integer(6))
+    );
+
+    // Operands are evaluated only when the operative chooses to, in the caller's environment.
+    interpreter.eval_str(
+        "(:= unless (operative (c then else) env
+            (? (eval c env) (eval else env) (eval then env))))",
+    )?;
+    assert_eq!(
+        interpreter.eval_str("({} ((y 2)) (unless (= y 1) (* y 10) (.< ())))")?,
+        Exp::Atom(Atom::Integer(20))
+    );
+    interpreter.eval_str(
+        "(::= all (operative (. forms) env
+            (? forms
+               (? (eval (.< forms) env) (eval (. 'all (.> forms)) env) ())
+               't)))",
+    )?;
+    assert_eq!(interpreter.eval_str("(all 1 (< x 2))")?.to_string(), "t");
+    assert_eq!(
+        interpreter.eval_str("(all 1 () (.< ()))")?,
+        Exp::List(List::Nil)
+    );
+
+    // The caller's environment is unchanged by the call.
+    interpreter.eval_str("(:= define-z (operative () env (eval '(:= z 3) env) z))")?;
+    assert_eq!(interpreter.eval_str("(define-z)")?, Exp::List(List::Nil));
+    assert_eq!(interpreter.eval_str("z")?, Exp::List(List::Nil));
+
+    assert_eq!(
+        interpreter.eval_str("(operative (a) env a)")?.to_string(),
+        "(operative (a) env a)"
+    );
+    assert_eq!(
+        interpreter.eval_str("(= quote-all quote-all)")?.to_string(),
+        "t"
+    );
+    let err = interpreter.eval_str("(unless 1 2)").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "unless expects 3 argument(s), but got 2"
+    );
+    let faulty_programs = vec![
+        "(operative (a) t a)",
+        "(operative (a) (env) a)",
+        "(operative a env a)",
+        "(operative (a))",
+    ];
+    for program in faulty_programs {
+        assert!(interpreter.eval_str(program).is_err(), "{program}");
+    }
+    Ok(())
+}
+
+#[test]
+fn string_operations() -> Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval_str("(:= s \"\\t Grüße, Welt \\n\")")?;
+    let programs = vec![
+        (r#"(string-concat "ab" "" "cd")"#, r#""abcd""#),
+        ("(string-concat)", r#""""#),
+        ("(string-length s)", "15"),
+        (r#"(string-length "")"#, "0"),
+        (r#"(substring "Grüße" 2 4)"#, r#""üß""#),
+        (r#"(substring "Grüße" 3)"#, r#""ße""#),
+        (r#"(substring "abc" 3)"#, r#""""#),
+        (r#"(string-index "Grüße" "ße")"#, "3"),
+        (r#"(string-index "abc" "d")"#, "()"),
+        (r#"(string-split "a,b,,c" ",")"#, r#"("a" "b" "" "c")"#),
+        (r#"(string-split "aß" "")"#, r#"("a" "ß")"#),
+        (r#"(string-join '("a" "b" "c") ", ")"#, r#""a, b, c""#),
+        (r#"(string-join () ", ")"#, r#""""#),
+        ("(string-trim s)", r#""Grüße, Welt""#),
+        (r#"(string-upper "Grüße")"#, r#""GRÜSSE""#),
+        (r#"(string-lower "ÄB")"#, r#""äb""#),
+        (r#"(string-replace "a-b-c" "-" "+")"#, r#""a+b+c""#),
+        (r#"(string-starts-with "hello" "he")"#, "t"),
+        (r#"(string-starts-with "hello" "lo")"#, "()"),
+        (r#"(string-ends-with "hello" "lo")"#, "t"),
+        (r#"(string->number "42")"#, "42"),
+        (r#"(string->number " -1/3 ")"#, "-1/3"),
+        (r#"(string->number "2.5")"#, "2.5"),
+        (r#"(string->number "+5")"#, "5"),
+        (r#"(string->number ".5")"#, "0.5"),
+        (r#"(string->number "2x")"#, "()"),
+        (r#"(string->number "")"#, "()"),
+        ("(number->string (/ 6 4))", r#""3/2""#),
+        ("(number->string 1.0)", r#""1.0""#),
+        ("(symbol->string 'abc)", r#""abc""#),
+        (r#"(string->symbol "abc")"#, "abc"),
+        (r#"(= (string->symbol "abc") 'abc)"#, "t"),
+    ];
+    for (program, expected) in programs {
+        assert_eq!(
+            interpreter.eval_str(program)?.to_string(),
+            expected,
+            "{program}"
+        );
+    }
+
+    let err = interpreter
+        .eval_str(r#"(substring "abc" 2 4)"#)
+        .unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "Index 4 is out of range for a string of length 3"
+    );
+    let err = interpreter.eval_str("(string-trim)").unwrap_err();
+    assert_eq!(
+        err.root().to_string(),
+        "string-trim expects 1 argument(s), but got 0"
+    );
+    let faulty_programs = vec![
+        r#"(substring "abc" 2 1)"#,
+        r#"(substring "abc" -1)"#,
+        r#"(string-concat "a" 'b)"#,
+        "(string-length 'abc)",
+        r#"(string-join '("a" b) "")"#,
+        r#"(number->string "1")"#,
+        r#"(symbol->string "a")"#,
+    ];
+    for program in faulty_programs {
+        assert!(interpreter.eval_str(program).is_err(), "{program}");
     }
+    Ok(())
 }